@@ -0,0 +1,65 @@
+use std::hint::black_box;
+
+use bytes::Bytes;
+use criterion::{Criterion, criterion_group, criterion_main};
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue};
+use spider_util::response::Response;
+use url::Url;
+
+fn page() -> String {
+    let mut html = String::from("<html><head><title>Catalogue</title></head><body><ul>");
+    for i in 0..500 {
+        html.push_str(&format!(
+            "<li class=\"product\"><a href=\"/item/{i}\">Item {i}</a>\
+             <span class=\"price\">{i}.99</span><p>Some description of item {i}.</p></li>"
+        ));
+    }
+    html.push_str("</ul></body></html>");
+    html
+}
+
+fn response(body: &Bytes) -> Response {
+    let url = Url::parse("https://example.com/catalogue").unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    Response::new(url, StatusCode::OK, headers, body.clone())
+}
+
+/// A typical parse callback: extract links, then query the page.
+fn callback(response: &Response) {
+    black_box(response.links());
+    black_box(response.css("li.product a::attr(href)").unwrap().get_all());
+    black_box(response.css(".price::text").unwrap().get_all());
+    black_box(response.base_url());
+}
+
+fn bench_dom_cache(c: &mut Criterion) {
+    let body = Bytes::from(page());
+    let mut group = c.benchmark_group("parse_callback");
+
+    group.bench_function("cached_document", |b| {
+        b.iter(|| callback(&response(&body)));
+    });
+
+    group.bench_function("reparse_per_helper", |b| {
+        b.iter(|| {
+            let response = response(&body);
+            black_box(response.links());
+            response.clear_parsed_documents();
+            black_box(response.css("li.product a::attr(href)").unwrap().get_all());
+            response.clear_parsed_documents();
+            black_box(response.css(".price::text").unwrap().get_all());
+            response.clear_parsed_documents();
+            black_box(response.base_url());
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_dom_cache);
+criterion_main!(benches);
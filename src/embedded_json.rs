@@ -0,0 +1,518 @@
+//! # Embedded JSON Module
+//!
+//! Extracts the application state that pages embed in `<script>` tags.
+//!
+//! ## Overview
+//!
+//! Server-rendered JavaScript applications ship their initial data with the
+//! page. `extract` finds it in:
+//! - JSON script tags such as
+//!   `<script id="__NEXT_DATA__" type="application/json">`, keyed by `id`;
+//! - assignments such as `window.__INITIAL_STATE__ = {...}`,
+//!   `window["__APOLLO_STATE__"] = {...}`, `var config = {...}` or
+//!   `x = JSON.parse('...')`, keyed by variable name;
+//! - Nuxt 2 state, `window.__NUXT__ = (function(a, b) { return {...} }(1, 2))`,
+//!   with the arguments substituted for the parameters;
+//! - Nuxt 3 `__NUXT_DATA__` payloads, revived from their `devalue`
+//!   serialization;
+//! - Next.js App Router `self.__next_f.push(...)` chunks, joined and split
+//!   into React Server Components rows under `__next_f`, keyed by row id.
+//!
+//! Literals are read with [`lenient_json`](crate::lenient_json), so single
+//! quotes, trailing commas, `undefined` and other JavaScript-isms are
+//! accepted. When a name is assigned several times, the first value that
+//! parses wins.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let state = response.embedded_json();
+//! if let Some(next) = state.get("__NEXT_DATA__") {
+//!     let props = &next["props"]["pageProps"];
+//! }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::iter;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::Html;
+use serde_json::{Map, Value};
+
+use crate::lenient_json;
+use crate::selector_cache::get_cached_selector;
+
+/// Assignments to a global or a declared variable.
+static ASSIGNMENT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?:\b(?:window|self|globalThis)\s*(?:\.\s*([A-Za-z_$][\w$]*)|\[\s*["']([^"'\]]+)["']\s*\])|\b(?:var|let|const)\s+([A-Za-z_$][\w$]*))\s*="#,
+    )
+    .expect("valid assignment regex")
+});
+
+/// The calls the Next.js App Router streams its payload through.
+static NEXT_F_PUSH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\bself\.__next_f\.push\(").expect("valid push regex"));
+
+/// How deeply `devalue` references are followed.
+const MAX_REVIVE_DEPTH: usize = 256;
+
+/// Extracts the JSON embedded in the scripts of a document, keyed by name.
+///
+/// JSON script tags without an `id` are keyed `json-script-0`,
+/// `json-script-1` and so on, in document order.
+pub fn extract(html: &Html) -> Map<String, Value> {
+    let mut found = Map::new();
+    let Some(selector) = get_cached_selector("script") else {
+        return found;
+    };
+
+    let mut flight = String::new();
+    let mut unnamed = 0;
+    for script in html.select(&selector) {
+        let text: String = script.text().collect();
+        let media_type = script
+            .value()
+            .attr("type")
+            .map(|value| {
+                value
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_ascii_lowercase()
+            })
+            .unwrap_or_default();
+
+        if is_json_type(&media_type) {
+            let Ok(value) = lenient_json::from_str(text.trim()) else {
+                continue;
+            };
+            let name = match script.value().id() {
+                Some(id) => id.to_owned(),
+                None => {
+                    unnamed += 1;
+                    format!("json-script-{}", unnamed - 1)
+                }
+            };
+            let value = if name == "__NUXT_DATA__" {
+                revive(&value)
+            } else {
+                value
+            };
+            found.entry(name).or_insert(value);
+        } else if is_javascript_type(&media_type) {
+            scan_script(&text, &mut found, &mut flight);
+        }
+    }
+
+    if !flight.is_empty() {
+        found
+            .entry("__next_f")
+            .or_insert_with(|| flight_rows(&flight));
+    }
+    found
+}
+
+fn is_json_type(media_type: &str) -> bool {
+    matches!(media_type, "application/json" | "text/json")
+        || (media_type.ends_with("+json") && media_type != "application/ld+json")
+}
+
+fn is_javascript_type(media_type: &str) -> bool {
+    matches!(
+        media_type,
+        "" | "module"
+            | "text/javascript"
+            | "application/javascript"
+            | "application/x-javascript"
+            | "text/ecmascript"
+            | "application/ecmascript"
+    )
+}
+
+/// Collects the assignments of a script, and its Next.js payload chunks
+/// into `flight`.
+fn scan_script(text: &str, found: &mut Map<String, Value>, flight: &mut String) {
+    for captures in ASSIGNMENT.captures_iter(text) {
+        let Some(name) = captures
+            .get(1)
+            .or_else(|| captures.get(2))
+            .or_else(|| captures.get(3))
+        else {
+            continue;
+        };
+        let rest = &text[captures.get(0).map_or(0, |m| m.end())..];
+        // A comparison, not an assignment.
+        if rest.starts_with('=') || found.contains_key(name.as_str()) {
+            continue;
+        }
+        if let Some(value) = assigned_value(rest) {
+            found.insert(name.as_str().to_owned(), value);
+        }
+    }
+
+    for call in NEXT_F_PUSH.find_iter(text) {
+        if let Ok((Value::Array(chunk), _)) = lenient_json::from_str_prefix(&text[call.end()..])
+            && chunk.first().and_then(Value::as_i64) == Some(1)
+            && let Some(Value::String(data)) = chunk.get(1)
+        {
+            flight.push_str(data);
+        }
+    }
+}
+
+/// Parses the value assigned by the source following an `=`.
+fn assigned_value(source: &str) -> Option<Value> {
+    let source = source.trim_start();
+    if let Some(args) = source.strip_prefix("JSON.parse(") {
+        let (encoded, _) = lenient_json::from_str_prefix(args).ok()?;
+        return lenient_json::from_str(encoded.as_str()?).ok();
+    }
+    if source.starts_with("(function") || source.starts_with("function") {
+        return function_call(source);
+    }
+    if source.starts_with(['{', '[']) {
+        return lenient_json::from_str_prefix(source)
+            .ok()
+            .map(|(value, _)| value);
+    }
+    None
+}
+
+/// Evaluates a function returning a literal called on literal arguments, as
+/// in Nuxt 2's `(function(a,b){return {...}}(1,"x"))`.
+fn function_call(source: &str) -> Option<Value> {
+    let source = source.strip_prefix('(').unwrap_or(source);
+    let rest = source.strip_prefix("function")?;
+    let open = rest.find('(')?;
+    let close = open + rest[open..].find(')')?;
+    let params: Vec<&str> = rest[open + 1..close]
+        .split(',')
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .collect();
+
+    // Statements before the `return` are skipped.
+    let block = rest[close + 1..].trim_start().strip_prefix('{')?;
+    let returned = &block[block.find("return")? + "return".len()..];
+
+    // Parse the returned literal once to find where the function ends.
+    let unknown: HashMap<&str, Value> = params.iter().map(|&param| (param, Value::Null)).collect();
+    let (_, len) = lenient_json::from_str_prefix_with(returned, &unknown).ok()?;
+    let after = returned[len..].trim_start();
+    let after = after.strip_prefix(';').unwrap_or(after).trim_start();
+    let after = after.strip_prefix('}')?.trim_start();
+    let after = after.strip_prefix(')').unwrap_or(after).trim_start();
+
+    let mut rest = after.strip_prefix('(')?;
+    let mut args = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with(')') {
+            break;
+        }
+        let (value, len) = lenient_json::from_str_prefix(rest).ok()?;
+        args.push(value);
+        rest = rest[len..].trim_start();
+        match rest.strip_prefix(',') {
+            Some(next) => rest = next,
+            None if rest.starts_with(')') => break,
+            None => return None,
+        }
+    }
+
+    let bindings: HashMap<&str, Value> = params
+        .into_iter()
+        .zip(args.into_iter().chain(iter::repeat(Value::Null)))
+        .collect();
+    lenient_json::from_str_prefix_with(returned, &bindings)
+        .ok()
+        .map(|(value, _)| value)
+}
+
+/// Revives a payload serialized with `devalue`: a flat array whose first
+/// item is the root value, nested values being indices into the array.
+fn revive(payload: &Value) -> Value {
+    match payload {
+        Value::Array(values) if !values.is_empty() => Reviver {
+            values,
+            revived: HashMap::new(),
+            visiting: HashSet::new(),
+        }
+        .revive(&Value::from(0), 0),
+        other => other.clone(),
+    }
+}
+
+struct Reviver<'a> {
+    values: &'a [Value],
+    revived: HashMap<usize, Value>,
+    visiting: HashSet<usize>,
+}
+
+impl Reviver<'_> {
+    fn revive(&mut self, index: &Value, depth: usize) -> Value {
+        let Some(index) = index.as_i64() else {
+            return Value::Null;
+        };
+        // Negative indices encode `undefined`, holes, NaN, infinities and -0.
+        if index == -6 {
+            return Value::from(0);
+        }
+        let Ok(index) = usize::try_from(index) else {
+            return Value::Null;
+        };
+        if let Some(value) = self.revived.get(&index) {
+            return value.clone();
+        }
+        // Cycles cannot be represented in JSON.
+        if depth > MAX_REVIVE_DEPTH || !self.visiting.insert(index) {
+            return Value::Null;
+        }
+
+        let values = self.values;
+        let value = match values.get(index) {
+            Some(Value::Array(items)) => match items.split_first() {
+                Some((Value::String(tag), args)) => self.revive_tagged(tag, args, depth),
+                _ => Value::Array(
+                    items
+                        .iter()
+                        .map(|item| self.revive(item, depth + 1))
+                        .collect(),
+                ),
+            },
+            Some(Value::Object(map)) => Value::Object(
+                map.iter()
+                    .map(|(key, item)| (key.clone(), self.revive(item, depth + 1)))
+                    .collect(),
+            ),
+            Some(primitive) => primitive.clone(),
+            None => Value::Null,
+        };
+
+        self.visiting.remove(&index);
+        self.revived.insert(index, value.clone());
+        value
+    }
+
+    /// Revives a special value such as `["Date", "..."]`, `["Set", 1, 2]` or
+    /// one of Nuxt's `["Reactive", 1]` wrappers.
+    fn revive_tagged(&mut self, tag: &str, args: &[Value], depth: usize) -> Value {
+        match (tag, args) {
+            ("Date" | "RegExp" | "BigInt" | "URL", [value, ..]) => value.clone(),
+            ("Set", items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.revive(item, depth + 1))
+                    .collect(),
+            ),
+            ("Map", entries) => Value::Object(
+                entries
+                    .chunks(2)
+                    .map(|entry| {
+                        let key = self.revive(&entry[0], depth + 1);
+                        let key = match key {
+                            Value::String(key) => key,
+                            other => other.to_string(),
+                        };
+                        let value = entry
+                            .get(1)
+                            .map_or(Value::Null, |item| self.revive(item, depth + 1));
+                        (key, value)
+                    })
+                    .collect(),
+            ),
+            ("null", entries) => Value::Object(
+                entries
+                    .chunks(2)
+                    .filter_map(|entry| {
+                        let key = entry[0].as_str()?.to_owned();
+                        let value = entry
+                            .get(1)
+                            .map_or(Value::Null, |item| self.revive(item, depth + 1));
+                        Some((key, value))
+                    })
+                    .collect(),
+            ),
+            (_, [value]) => self.revive(value, depth + 1),
+            _ => Value::Null,
+        }
+    }
+}
+
+/// Splits a React Server Components payload into its rows, keyed by row id.
+///
+/// Rows holding JSON are parsed, text rows are kept as strings.
+fn flight_rows(payload: &str) -> Value {
+    let mut rows = Map::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        let (id, body) = match rest.split_once(':') {
+            Some((id, body)) if !id.is_empty() && id.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                (id, body)
+            }
+            _ => {
+                rest = rest.split_once('\n').map_or("", |(_, next)| next);
+                continue;
+            }
+        };
+
+        // Text rows, `T<hex length>,<text>`, may span several lines.
+        if let Some(text_row) = body.strip_prefix('T')
+            && let Some((len, text)) = text_row.split_once(',')
+            && let Ok(len) = usize::from_str_radix(len, 16)
+        {
+            let mut end = len.min(text.len());
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            rows.insert(id.to_owned(), Value::String(text[..end].to_owned()));
+            rest = &text[end..];
+            continue;
+        }
+
+        let (line, next) = body.split_once('\n').unwrap_or((body, ""));
+        // Rows may start with a type tag, as in `I[...]` or `HL[...]`.
+        let json = line.trim_start_matches(|c: char| c.is_ascii_uppercase());
+        let value = lenient_json::from_str(json).unwrap_or_else(|_| Value::String(line.to_owned()));
+        rows.insert(id.to_owned(), value);
+        rest = next;
+    }
+    Value::Object(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NEXT_DATA: &str = r#"<html><head>
+        <script id="__NEXT_DATA__" type="application/json">{"props": {"pageProps": {"id": 7}}}</script>
+        <script type="application/json">[1, 2,]</script>
+        <script type="application/ld+json">{"@type": "Product"}</script>
+        </head></html>"#;
+
+    const ASSIGNMENTS: &str = r#"<script>
+        if (window.__INITIAL_STATE__ == {}) {}
+        window.__INITIAL_STATE__ = {user: {name: 'Ada', roles: ['admin',],}, token: undefined};
+        window["__APOLLO_STATE__"] = {"Book:1": {title: "Dune"}};
+        var config = {debug: false};
+        const data = JSON.parse('{"k": [1, 2]}');
+        window.__INITIAL_STATE__ = {overwritten: true};
+        let broken = {a: 1,
+        </script>"#;
+
+    const NUXT_2: &str = r#"<script>window.__NUXT__=(function(a,b,c){var d = 1;return {layout:"default",data:[{title:a,count:b,missing:c}]}}("Hello",3));</script>"#;
+
+    const NUXT_3: &str = r#"<script type="application/json" id="__NUXT_DATA__">[["ShallowReactive",1],{"data":2,"state":4},["ShallowReactive",3],{"title":5},{},"Hello"]</script>"#;
+
+    const NEXT_FLIGHT: &str = r#"<script>self.__next_f.push([0])</script>
+        <script>self.__next_f.push([1,"0:{\"a\":1}\n1:I[\"chunk\"]\n"])</script>
+        <script>self.__next_f.push([1,"2:T5,hello3:\"x\"\n"])</script>"#;
+
+    fn extract_from(html: &str) -> Map<String, Value> {
+        extract(&Html::parse_document(html))
+    }
+
+    #[test]
+    fn json_script_tags() {
+        let found = extract_from(NEXT_DATA);
+        assert_eq!(found["__NEXT_DATA__"]["props"]["pageProps"]["id"], 7);
+        assert_eq!(found["json-script-0"], json!([1, 2]));
+        assert_eq!(found.len(), 2);
+    }
+
+    #[test]
+    fn assignments() {
+        let found = extract_from(ASSIGNMENTS);
+        assert_eq!(
+            found["__INITIAL_STATE__"],
+            json!({ "user": { "name": "Ada", "roles": ["admin"] }, "token": null })
+        );
+        assert_eq!(found["__APOLLO_STATE__"]["Book:1"]["title"], "Dune");
+        assert_eq!(found["config"], json!({ "debug": false }));
+        assert_eq!(found["data"], json!({ "k": [1, 2] }));
+        assert!(!found.contains_key("broken"));
+    }
+
+    #[test]
+    fn nuxt_2_function_state() {
+        let found = extract_from(NUXT_2);
+        assert_eq!(
+            found["__NUXT__"],
+            json!({
+                "layout": "default",
+                "data": [{ "title": "Hello", "count": 3, "missing": null }],
+            })
+        );
+    }
+
+    #[test]
+    fn nuxt_3_devalue_payload() {
+        let found = extract_from(NUXT_3);
+        assert_eq!(
+            found["__NUXT_DATA__"],
+            json!({ "data": { "title": "Hello" }, "state": {} })
+        );
+    }
+
+    #[test]
+    fn devalue_special_values() {
+        let payload = json!([
+            { "when": 1, "tags": 2, "lookup": 4, "self": 0, "gone": -1, "zero": -6, "dangling": 99 },
+            ["Date", "2024-01-01T00:00:00.000Z"],
+            ["Set", 3, 3],
+            "a",
+            ["Map", 3, 5],
+            1.5,
+        ]);
+        assert_eq!(
+            revive(&payload),
+            json!({
+                "when": "2024-01-01T00:00:00.000Z",
+                "tags": ["a", "a"],
+                "lookup": { "a": 1.5 },
+                "self": null,
+                "gone": null,
+                "zero": 0,
+                "dangling": null,
+            })
+        );
+        assert_eq!(revive(&json!([])), json!([]));
+        assert_eq!(revive(&json!({ "a": 1 })), json!({ "a": 1 }));
+    }
+
+    #[test]
+    fn next_flight_rows() {
+        let found = extract_from(NEXT_FLIGHT);
+        assert_eq!(
+            found["__next_f"],
+            json!({ "0": { "a": 1 }, "1": ["chunk"], "2": "hello", "3": "x" })
+        );
+    }
+
+    #[test]
+    fn malformed_scripts_are_skipped() {
+        let found = extract_from(
+            r#"<script type="application/json" id="bad">{"a": </script>
+            <script>window.__A__ = JSON.parse('{oops');</script>
+            <script>window.__B__ = (function(a){return {x: a}}("unterminated);</script>
+            <script>window.__C__ = function(a){return {x: a</script>
+            <script>window.__D__ = 42;</script>
+            <script>self.__next_f.push([1, "unterminated</script>"#,
+        );
+        assert!(found.is_empty(), "{found:?}");
+    }
+
+    #[test]
+    fn truncated_documents_do_not_panic() {
+        for html in [NEXT_DATA, ASSIGNMENTS, NUXT_2, NUXT_3, NEXT_FLIGHT] {
+            for end in (0..html.len()).filter(|&end| html.is_char_boundary(end)) {
+                extract_from(&html[..end]);
+            }
+        }
+        assert!(flight_rows("zz:no id\n1:T ff,short").is_object());
+    }
+}
@@ -61,6 +61,12 @@ pub enum SpiderError {
     HeaderValueError(String),
     #[error("HTML parsing error: {0}")]
     HtmlParseError(String),
+    #[error("XML parsing error: {0}")]
+    XmlParseError(String),
+    #[error("XPath error: {0}")]
+    XPathError(String),
+    #[error("JSONPath error: {0}")]
+    JsonPathError(String),
     #[error("UTF-8 parsing error: {0}")]
     Utf8Error(#[from] Utf8Error),
     #[error("Pipeline error: {0}")]
@@ -81,6 +87,12 @@ impl From<bincode::Error> for SpiderError {
     }
 }
 
+impl From<csv::Error> for SpiderError {
+    fn from(err: csv::Error) -> Self {
+        SpiderError::GeneralError(format!("CSV error: {}", err))
+    }
+}
+
 impl From<reqwest::Error> for SpiderError {
     fn from(err: reqwest::Error) -> Self {
         SpiderError::ReqwestError(err.into())
@@ -93,6 +105,12 @@ impl From<std::io::Error> for SpiderError {
     }
 }
 
+impl From<quick_xml::Error> for SpiderError {
+    fn from(err: quick_xml::Error) -> Self {
+        SpiderError::XmlParseError(err.to_string())
+    }
+}
+
 impl From<SerdeJsonError> for SpiderError {
     fn from(err: SerdeJsonError) -> Self {
         SpiderError::JsonError(err.to_string())
@@ -0,0 +1,502 @@
+//! # Feed Module
+//!
+//! Parses RSS 2.0, RSS 1.0 (RDF) and Atom feeds into a common shape.
+//!
+//! ## Overview
+//!
+//! `Feed::parse` detects the feed format from its root element and returns a
+//! `Feed` whose `FeedEntry` values expose the same fields whatever the
+//! format: link, title, guid, summary, publication and update dates and
+//! enclosures. Dates are kept as written, which is RFC 822 for RSS and RFC
+//! 3339 for Atom and Dublin Core dates.
+//!
+//! `Response::feed` parses a feed response and `Response::feed_links` finds
+//! the feeds an HTML page advertises through
+//! `<link rel="alternate" type="application/rss+xml">` elements.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let feed = response.feed()?;
+//! for entry in &feed.entries {
+//!     if let Some(link) = &entry.link {
+//!         println!("{} {:?}", link, entry.published);
+//!     }
+//! }
+//! ```
+
+use quick_xml::Reader;
+use quick_xml::XmlVersion;
+use quick_xml::events::{BytesStart, Event};
+use scraper::Html;
+use url::Url;
+
+use crate::error::SpiderError;
+use crate::selector_cache::get_cached_selector;
+use crate::utils::resolve_xml_reference;
+
+/// The MIME types of feeds advertised by HTML pages.
+pub const FEED_MEDIA_TYPES: &[&str] = &[
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/rdf+xml",
+];
+
+/// The format of a feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedKind {
+    /// An RSS 0.9x or 2.0 feed (`<rss>`).
+    Rss,
+    /// An RSS 1.0 feed (`<rdf:RDF>`).
+    Rdf,
+    /// An Atom feed (`<feed>`).
+    Atom,
+}
+
+/// A parsed feed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feed {
+    /// The format of the feed.
+    pub kind: FeedKind,
+    /// The `<title>` of the channel or feed.
+    pub title: Option<String>,
+    /// The website the feed belongs to: the `<link>` of an RSS channel or
+    /// the alternate `<link>` of an Atom feed.
+    pub link: Option<Url>,
+    /// The `<description>` of an RSS channel or the `<subtitle>` of an Atom
+    /// feed.
+    pub description: Option<String>,
+    /// The items or entries, in document order.
+    pub entries: Vec<FeedEntry>,
+}
+
+/// An item of an RSS feed or an entry of an Atom feed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedEntry {
+    /// The `<title>` of the entry.
+    pub title: Option<String>,
+    /// The `<link>` of the entry, falling back to its guid when that is a
+    /// permalink.
+    pub link: Option<Url>,
+    /// The `<guid>` of an RSS item or the `<id>` of an Atom entry.
+    pub guid: Option<String>,
+    /// The `<description>` of an RSS item or the `<summary>` of an Atom entry,
+    /// falling back to its content.
+    pub summary: Option<String>,
+    /// The publication date (`<pubDate>`, `<published>` or `<dc:date>`), as
+    /// written.
+    pub published: Option<String>,
+    /// The date of the last update (`<updated>`), as written.
+    pub updated: Option<String>,
+    /// The files attached to the entry.
+    pub enclosures: Vec<Enclosure>,
+}
+
+/// A file attached to a feed entry, such as a podcast episode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enclosure {
+    /// The URL of the file.
+    pub url: Url,
+    /// The MIME type of the file.
+    pub media_type: Option<String>,
+    /// The size of the file in bytes.
+    pub length: Option<u64>,
+}
+
+/// A feed advertised by an HTML page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedLink {
+    /// The URL of the feed, resolved against the page.
+    pub url: Url,
+    /// The MIME type the page gives the feed, one of [`FEED_MEDIA_TYPES`].
+    pub media_type: String,
+    /// The `title` of the `<link>` element.
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Link,
+    Guid,
+    Summary,
+    Content,
+    Published,
+    Date,
+    Updated,
+}
+
+/// The text of an element being read, and the depth it ends at.
+struct Capture {
+    field: Field,
+    depth: usize,
+    text: String,
+}
+
+/// An entry being read, with the depth of its children, its content and
+/// whether its guid is a permalink.
+struct PendingEntry {
+    entry: FeedEntry,
+    depth: usize,
+    content: Option<String>,
+    guid_is_permalink: bool,
+}
+
+impl Feed {
+    /// Parses an RSS, RDF or Atom document. Relative links are resolved
+    /// against `base`.
+    ///
+    /// Returns [`SpiderError::XmlParseError`] if the document is not
+    /// well-formed or is not a feed.
+    pub fn parse(text: &str, base: &Url) -> Result<Self, SpiderError> {
+        let mut reader = Reader::from_str(text);
+        let mut feed: Option<Feed> = None;
+        let mut path: Vec<String> = Vec::new();
+        let mut pending: Option<PendingEntry> = None;
+        let mut capture: Option<Capture> = None;
+
+        loop {
+            let event = reader.read_event()?;
+            match &event {
+                Event::Start(start) | Event::Empty(start) => {
+                    let name = start.local_name().as_ref().to_owned();
+
+                    match feed.as_mut() {
+                        None => feed = Some(Feed::new(&name)?),
+                        // Markup inside a captured field, such as Atom XHTML content.
+                        Some(_) if capture.is_some() => {}
+                        Some(feed) => {
+                            let depth = path.len() + 1;
+                            let field = match pending.as_mut() {
+                                None if matches!(name.as_str(), "item" | "entry") => {
+                                    // RDF items name their page in `rdf:about`.
+                                    let entry = FeedEntry {
+                                        link: attribute(start, "about")
+                                            .and_then(|about| resolve(base, &about)),
+                                        ..FeedEntry::default()
+                                    };
+                                    pending = Some(PendingEntry {
+                                        entry,
+                                        depth: depth + 1,
+                                        content: None,
+                                        guid_is_permalink: true,
+                                    });
+                                    None
+                                }
+                                Some(current) if current.depth == depth => {
+                                    if name == "guid" {
+                                        current.guid_is_permalink =
+                                            !attribute(start, "isPermaLink")
+                                                .is_some_and(|v| v.eq_ignore_ascii_case("false"));
+                                    }
+                                    entry_element(&mut current.entry, &name, start, base)
+                                }
+                                None if is_channel(feed.kind, &path) => {
+                                    channel_element(feed, &name, start, base)
+                                }
+                                _ => None,
+                            };
+                            if !matches!(event, Event::Empty(_)) {
+                                capture = field.map(|field| Capture {
+                                    field,
+                                    depth,
+                                    text: String::new(),
+                                });
+                            }
+                        }
+                    }
+
+                    if matches!(event, Event::Start(_)) {
+                        path.push(name);
+                    }
+                }
+                Event::Text(text) => {
+                    if let Some(capture) = capture.as_mut() {
+                        capture.text.push_str(&text.xml10_content());
+                    }
+                }
+                Event::CData(text) => {
+                    if let Some(capture) = capture.as_mut() {
+                        capture.text.push_str(&text.xml10_content());
+                    }
+                }
+                Event::GeneralRef(reference) => {
+                    if let Some(capture) = capture.as_mut() {
+                        capture.text.push_str(&resolve_xml_reference(reference));
+                    }
+                }
+                Event::End(_) => {
+                    let depth = path.len();
+                    path.pop();
+                    let Some(feed) = feed.as_mut() else {
+                        continue;
+                    };
+
+                    if let Some(done) = capture.take_if(|c| c.depth == depth) {
+                        let value = done.text.trim();
+                        if value.is_empty() {
+                            continue;
+                        }
+                        match pending.as_mut() {
+                            Some(current) => current.set(done.field, value, base),
+                            None => feed.set(done.field, value, base),
+                        }
+                    } else if let Some(current) = pending.take_if(|p| p.depth == depth + 1) {
+                        feed.entries.push(current.finish());
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        feed.ok_or_else(|| SpiderError::XmlParseError("the document is empty".to_owned()))
+    }
+
+    fn new(root: &str) -> Result<Self, SpiderError> {
+        let kind = match root {
+            "rss" => FeedKind::Rss,
+            "RDF" => FeedKind::Rdf,
+            "feed" => FeedKind::Atom,
+            _ => {
+                return Err(SpiderError::XmlParseError(format!(
+                    "<{root}> is not the root of an RSS, RDF or Atom feed"
+                )));
+            }
+        };
+        Ok(Self {
+            kind,
+            title: None,
+            link: None,
+            description: None,
+            entries: Vec::new(),
+        })
+    }
+
+    fn set(&mut self, field: Field, value: &str, base: &Url) {
+        match field {
+            Field::Title => self.title = Some(value.to_owned()),
+            Field::Link => self.link = resolve(base, value).or(self.link.take()),
+            Field::Summary => self.description = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+}
+
+impl PendingEntry {
+    fn set(&mut self, field: Field, value: &str, base: &Url) {
+        let entry = &mut self.entry;
+        let value = Some(value.to_owned());
+        match field {
+            Field::Title => entry.title = value,
+            Field::Link => {
+                if let Some(url) = value.as_deref().and_then(|v| resolve(base, v)) {
+                    entry.link = Some(url);
+                }
+            }
+            Field::Guid => entry.guid = value,
+            Field::Summary => entry.summary = value,
+            Field::Content => self.content = value,
+            Field::Published => entry.published = value,
+            Field::Date => {
+                if entry.published.is_none() {
+                    entry.published = value;
+                }
+            }
+            Field::Updated => entry.updated = value,
+        }
+    }
+
+    fn finish(self) -> FeedEntry {
+        let mut entry = self.entry;
+        if entry.summary.is_none() {
+            entry.summary = self.content;
+        }
+        // An RSS guid is a permalink unless `isPermaLink="false"` says otherwise.
+        if entry.link.is_none() && self.guid_is_permalink {
+            entry.link = entry
+                .guid
+                .as_deref()
+                .and_then(|guid| Url::parse(guid).ok())
+                .filter(|url| matches!(url.scheme(), "http" | "https"));
+        }
+        entry
+    }
+}
+
+/// Returns `true` if `path` leads to the element holding the feed's own fields.
+fn is_channel(kind: FeedKind, path: &[String]) -> bool {
+    match kind {
+        FeedKind::Rss | FeedKind::Rdf => path.len() == 2 && path[1] == "channel",
+        FeedKind::Atom => path.len() == 1,
+    }
+}
+
+/// Handles a child of the feed's channel, returning the field its text sets.
+fn channel_element(
+    feed: &mut Feed,
+    name: &str,
+    start: &BytesStart<'_>,
+    base: &Url,
+) -> Option<Field> {
+    match name {
+        "title" => Some(Field::Title),
+        "description" | "subtitle" => Some(Field::Summary),
+        "link" => match attribute(start, "href") {
+            // An Atom link, also used by RSS feeds for `rel="self"`.
+            Some(href) => {
+                if feed.link.is_none() && is_alternate(start) {
+                    feed.link = resolve(base, &href);
+                }
+                None
+            }
+            None => Some(Field::Link),
+        },
+        _ => None,
+    }
+}
+
+/// Handles a child of a feed entry, returning the field its text sets.
+fn entry_element(
+    entry: &mut FeedEntry,
+    name: &str,
+    start: &BytesStart<'_>,
+    base: &Url,
+) -> Option<Field> {
+    match name {
+        "title" => Some(Field::Title),
+        "guid" | "id" | "identifier" => Some(Field::Guid),
+        "description" | "summary" => Some(Field::Summary),
+        "pubDate" | "published" | "issued" => Some(Field::Published),
+        "date" => Some(Field::Date),
+        "updated" | "modified" => Some(Field::Updated),
+        "link" => match attribute(start, "href") {
+            Some(href) => {
+                let rel = attribute(start, "rel");
+                if rel.as_deref() == Some("enclosure") {
+                    push_enclosure(entry, start, &href, base);
+                } else if entry.link.is_none() && is_alternate(start) {
+                    entry.link = resolve(base, &href);
+                }
+                None
+            }
+            None => Some(Field::Link),
+        },
+        // `media:content` carries a `url`, Atom `content` does not.
+        "enclosure" | "content" if attribute(start, "url").is_some() => {
+            let url = attribute(start, "url").unwrap_or_default();
+            push_enclosure(entry, start, &url, base);
+            None
+        }
+        "content" | "encoded" => Some(Field::Content),
+        _ => None,
+    }
+}
+
+fn push_enclosure(entry: &mut FeedEntry, start: &BytesStart<'_>, href: &str, base: &Url) {
+    if let Some(url) = resolve(base, href) {
+        entry.enclosures.push(Enclosure {
+            url,
+            media_type: attribute(start, "type"),
+            length: attribute(start, "length")
+                .or_else(|| attribute(start, "fileSize"))
+                .and_then(|length| length.parse().ok()),
+        });
+    }
+}
+
+fn is_alternate(link: &BytesStart<'_>) -> bool {
+    attribute(link, "rel").is_none_or(|rel| rel.eq_ignore_ascii_case("alternate"))
+}
+
+/// Returns the trimmed value of the attribute with the given local name.
+fn attribute(start: &BytesStart<'_>, name: &str) -> Option<String> {
+    start
+        .attributes()
+        .flatten()
+        .find(|attr| attr.key.local_name().as_ref() == name)
+        .and_then(|attr| attr.normalized_value(XmlVersion::Implicit1_0).ok())
+        .map(|value| value.trim().to_owned())
+}
+
+fn resolve(base: &Url, href: &str) -> Option<Url> {
+    base.join(href.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Finds the feeds advertised by an HTML document.
+pub(crate) fn feed_links(html: &Html, base: &Url) -> Vec<FeedLink> {
+    let Some(selector) = get_cached_selector("link[rel][href][type]") else {
+        return Vec::new();
+    };
+
+    let mut links: Vec<FeedLink> = Vec::new();
+    for element in html.select(&selector) {
+        let element = element.value();
+        let rel = element.attr("rel").unwrap_or_default();
+        if !rel
+            .split_ascii_whitespace()
+            .any(|token| token.eq_ignore_ascii_case("alternate"))
+        {
+            continue;
+        }
+        let media_type = element
+            .attr("type")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if !FEED_MEDIA_TYPES.contains(&media_type.as_str()) {
+            continue;
+        }
+        if let Some(url) = element.attr("href").and_then(|href| resolve(base, href))
+            && !links.iter().any(|link| link.url == url)
+        {
+            links.push(FeedLink {
+                url,
+                media_type,
+                title: element
+                    .attr("title")
+                    .map(str::trim)
+                    .filter(|title| !title.is_empty())
+                    .map(str::to_owned),
+            });
+        }
+    }
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_items(items: &str) -> Vec<FeedEntry> {
+        let text = format!("<rss version=\"2.0\"><channel><title>T</title>{items}</channel></rss>");
+        let base = Url::parse("https://example.com/feed.xml").unwrap();
+        Feed::parse(&text, &base).unwrap().entries
+    }
+
+    #[test]
+    fn guid_is_the_link_only_when_it_is_a_permalink() {
+        let entries = parse_items(
+            r#"<item><guid>https://example.com/a</guid></item>
+            <item><guid isPermaLink="true">https://example.com/b</guid></item>
+            <item><guid isPermaLink="false">https://example.com/c</guid></item>
+            <item><link>https://example.com/d</link><guid isPermaLink="false">x</guid></item>"#,
+        );
+        let links: Vec<Option<&str>> = entries
+            .iter()
+            .map(|entry| entry.link.as_ref().map(Url::as_str))
+            .collect();
+        assert_eq!(
+            links,
+            [
+                Some("https://example.com/a"),
+                Some("https://example.com/b"),
+                None,
+                Some("https://example.com/d"),
+            ]
+        );
+        assert_eq!(entries[2].guid.as_deref(), Some("https://example.com/c"));
+    }
+}
@@ -0,0 +1,507 @@
+//! Request fingerprinting for deduplication and caching in `spider-lib`.
+//!
+//! This module defines the `RequestFingerprinter` trait, which decides which
+//! parts of a `Request` identify it, together with two implementations:
+//! - `DefaultFingerprinter`, which hashes the URL, method and body exactly
+//!   like `Request::fingerprint`.
+//! - `ConfigurableFingerprinter`, which can additionally canonicalize the URL,
+//!   ignore selected query parameters and include selected headers or
+//!   metadata entries.
+//!
+//! Fingerprints can be produced as a hex `String`, a raw `u64` or a 128-bit
+//! `[u8; 16]` digest.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use spider_util::fingerprint::{ConfigurableFingerprinter, RequestFingerprinter};
+//!
+//! let fingerprinter = ConfigurableFingerprinter::new()
+//!     .with_header("Accept-Language")
+//!     .with_ignored_query_param("sessionid");
+//!
+//! let key: u64 = fingerprinter.fingerprint_u64(&request);
+//! ```
+
+use std::hash::Hasher;
+
+use serde_json::Value;
+
+use twox_hash::{XxHash3_128, XxHash64};
+use url::Url;
+use url::form_urlencoded;
+
+use crate::request::{Body, Request};
+use crate::utils::{CanonicalizeOptions, canonicalize_url};
+
+/// Decides which parts of a `Request` make up its fingerprint.
+pub trait RequestFingerprinter: Send + Sync {
+    /// Feeds the identifying parts of `request` into `state`.
+    fn hash_request(&self, request: &Request, state: &mut dyn Hasher);
+
+    /// Computes a 64-bit fingerprint of the request.
+    fn fingerprint_u64(&self, request: &Request) -> u64 {
+        let mut hasher = XxHash64::default();
+        self.hash_request(request, &mut hasher);
+        hasher.finish()
+    }
+
+    /// Computes a 128-bit fingerprint of the request, as big-endian bytes.
+    fn fingerprint_bytes(&self, request: &Request) -> [u8; 16] {
+        let mut hasher = Hasher128(XxHash3_128::new());
+        self.hash_request(request, &mut hasher);
+        hasher.0.finish_128().to_be_bytes()
+    }
+
+    /// Computes the fingerprint of the request as a lowercase hex string.
+    fn fingerprint(&self, request: &Request) -> String {
+        format!("{:x}", self.fingerprint_u64(request))
+    }
+}
+
+/// Adapts the 128-bit xxHash3 hasher to `std::hash::Hasher`.
+struct Hasher128(XxHash3_128);
+
+impl Hasher for Hasher128 {
+    fn finish(&self) -> u64 {
+        self.0.finish_128() as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+    }
+}
+
+/// Fingerprints a request by its URL, method and body.
+///
+/// This is the fingerprinter used by `Request::fingerprint`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFingerprinter;
+
+impl RequestFingerprinter for DefaultFingerprinter {
+    fn hash_request(&self, request: &Request, state: &mut dyn Hasher) {
+        state.write(request.url.as_str().as_bytes());
+        state.write(request.method.as_str().as_bytes());
+        hash_body(request.body.as_ref(), state);
+    }
+}
+
+/// A fingerprinter that can be tuned per site or API.
+///
+/// With no options set it produces the same fingerprints as
+/// `DefaultFingerprinter`.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigurableFingerprinter {
+    canonicalize: Option<CanonicalizeOptions>,
+    headers: Vec<String>,
+    meta_keys: Vec<String>,
+    ignored_query_params: Vec<String>,
+}
+
+impl ConfigurableFingerprinter {
+    /// Creates a new `ConfigurableFingerprinter` with no options set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hashes the canonical form of the URL instead of the URL as-is.
+    pub fn with_canonicalization(mut self, options: CanonicalizeOptions) -> Self {
+        self.canonicalize = Some(options);
+        self
+    }
+
+    /// Includes all values of the given header in the fingerprint.
+    pub fn with_header(mut self, name: &str) -> Self {
+        self.headers.push(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Includes the given metadata entry in the fingerprint.
+    pub fn with_meta_key(mut self, key: &str) -> Self {
+        self.meta_keys.push(key.to_owned());
+        self
+    }
+
+    /// Excludes the given query parameter from the fingerprinted URL.
+    pub fn with_ignored_query_param(mut self, name: &str) -> Self {
+        self.ignored_query_params.push(name.to_owned());
+        self
+    }
+
+    fn effective_url(&self, url: &Url) -> Url {
+        let mut url = match &self.canonicalize {
+            Some(options) => canonicalize_url(url, options),
+            None => url.clone(),
+        };
+
+        if !self.ignored_query_params.is_empty()
+            && let Some(query) = url.query()
+        {
+            let kept: Vec<&str> = query
+                .split('&')
+                .filter(|pair| {
+                    let key = form_urlencoded::parse(pair.as_bytes())
+                        .next()
+                        .map(|(key, _)| key);
+                    !key.is_some_and(|key| self.ignored_query_params.iter().any(|p| *p == key))
+                })
+                .collect();
+            if kept.is_empty() {
+                url.set_query(None);
+            } else {
+                url.set_query(Some(&kept.join("&")));
+            }
+        }
+
+        url
+    }
+}
+
+impl RequestFingerprinter for ConfigurableFingerprinter {
+    fn hash_request(&self, request: &Request, state: &mut dyn Hasher) {
+        state.write(self.effective_url(&request.url).as_str().as_bytes());
+        state.write(request.method.as_str().as_bytes());
+        hash_body(request.body.as_ref(), state);
+
+        for name in &self.headers {
+            write_prefixed(state, name.as_bytes());
+            let values = request.headers.get_all(name.as_str());
+            state.write(&(values.iter().count() as u64).to_le_bytes());
+            for value in values {
+                write_prefixed(state, value.as_bytes());
+            }
+        }
+
+        for key in &self.meta_keys {
+            write_prefixed(state, key.as_bytes());
+            let value = request
+                .meta
+                .get(key.as_str())
+                .map(|v| v.value().to_string());
+            write_optional(state, value.as_deref());
+        }
+    }
+}
+
+/// Writes `bytes` preceded by their length, so adjacent fields cannot collide.
+fn write_prefixed(state: &mut dyn Hasher, bytes: &[u8]) {
+    state.write(&(bytes.len() as u64).to_le_bytes());
+    state.write(bytes);
+}
+
+fn write_optional(state: &mut dyn Hasher, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            state.write(&[1]);
+            write_prefixed(state, value.as_bytes());
+        }
+        None => state.write(&[0]),
+    }
+}
+
+fn hash_body(body: Option<&Body>, state: &mut dyn Hasher) {
+    let Some(body) = body else {
+        return;
+    };
+
+    match body {
+        Body::Json(json_val) => {
+            let mut serialized = String::new();
+            write_canonical_json(json_val, &mut serialized);
+            state.write(serialized.as_bytes());
+        }
+        Body::Form(form_val) => {
+            state.write(&(form_val.len() as u64).to_le_bytes());
+            for (key, value) in form_val.iter() {
+                write_prefixed(state, key.as_bytes());
+                write_prefixed(state, value.as_bytes());
+            }
+        }
+        Body::Bytes(bytes_val) => {
+            state.write(bytes_val);
+        }
+        Body::Multipart(multipart) => {
+            // The boundary is random, so only the parts are hashed.
+            state.write(&(multipart.parts().len() as u64).to_le_bytes());
+            for part in multipart.parts() {
+                write_prefixed(state, part.name.as_bytes());
+                write_optional(state, part.filename.as_deref());
+                write_optional(state, part.content_type.as_deref());
+                write_prefixed(state, &part.data);
+            }
+        }
+    }
+}
+
+/// Serializes `value` as compact JSON with the keys of every object sorted,
+/// so that its fingerprint does not depend on the order keys were inserted
+/// in, even when `serde_json` keeps that order (its `preserve_order`
+/// feature may be enabled by any crate of the build).
+fn write_canonical_json(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical_json(item, out);
+            }
+            out.push('}');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use reqwest::Method;
+    use reqwest::header::HeaderValue;
+    use serde_json::json;
+
+    use super::*;
+    use crate::request::{FormData, Multipart};
+
+    fn request(url: &str) -> Request {
+        Request::new(Url::parse(url).unwrap())
+    }
+
+    fn form(fields: &[(&str, &str)]) -> Request {
+        let form = fields.iter().fold(FormData::new(), |form, (key, value)| {
+            form.with_field(*key, *value)
+        });
+        request("https://example.com/submit").with_form(form)
+    }
+
+    #[test]
+    fn default_fingerprints_cover_url_method_and_body() {
+        let get = request("https://example.com/a");
+        assert_eq!(
+            get.fingerprint(),
+            request("https://example.com/a").fingerprint()
+        );
+        assert_ne!(
+            get.fingerprint(),
+            request("https://example.com/b").fingerprint()
+        );
+        assert_ne!(
+            get.fingerprint(),
+            request("https://example.com/a")
+                .with_method(Method::HEAD)
+                .fingerprint()
+        );
+        assert_ne!(
+            get.fingerprint(),
+            request("https://example.com/a")
+                .with_bytes(Bytes::from_static(b"x"))
+                .with_method(Method::GET)
+                .fingerprint()
+        );
+
+        // Headers and metadata are ignored unless configured.
+        let decorated = request("https://example.com/a")
+            .with_header("Accept-Language", "fr")
+            .unwrap()
+            .with_meta("page", json!(2));
+        assert_eq!(get.fingerprint(), decorated.fingerprint());
+
+        let configurable = ConfigurableFingerprinter::new();
+        assert_eq!(configurable.fingerprint(&get), get.fingerprint());
+        assert_eq!(
+            configurable.fingerprint(&get),
+            format!("{:x}", DefaultFingerprinter.fingerprint_u64(&get))
+        );
+        assert_eq!(
+            configurable.fingerprint_bytes(&get),
+            DefaultFingerprinter.fingerprint_bytes(&get)
+        );
+        assert_ne!(
+            DefaultFingerprinter.fingerprint_bytes(&get),
+            DefaultFingerprinter.fingerprint_bytes(&decorated.with_method(Method::POST))
+        );
+    }
+
+    #[test]
+    fn json_bodies_ignore_key_order() {
+        let value = json!({ "b": [1, { "y": null, "x": "\u{e9}\"" }], "a": 1.5 });
+        let reordered: Value =
+            serde_json::from_str(r#"{"a": 1.5, "b": [1, {"x": "\u00e9\"", "y": null}]}"#).unwrap();
+        assert_eq!(
+            request("https://example.com/api")
+                .with_json(value.clone())
+                .fingerprint(),
+            request("https://example.com/api")
+                .with_json(reordered)
+                .fingerprint()
+        );
+        assert_ne!(
+            request("https://example.com/api")
+                .with_json(json!({ "a": 1 }))
+                .fingerprint(),
+            request("https://example.com/api")
+                .with_json(json!({ "a": "1" }))
+                .fingerprint()
+        );
+
+        // Sorted output is plain compact JSON, so fingerprints stay stable.
+        let mut canonical = String::new();
+        write_canonical_json(&value, &mut canonical);
+        assert_eq!(canonical, r#"{"a":1.5,"b":[1,{"x":"é\"","y":null}]}"#);
+    }
+
+    #[test]
+    fn form_fields_cannot_run_into_each_other() {
+        let cases = [
+            (&[("ab", "c")][..], &[("a", "bc")][..]),
+            (&[("a", "b"), ("c", "d")][..], &[("a", "b&c=d")][..]),
+            (&[("a", "")][..], &[("", "a")][..]),
+            (&[("a", "b")][..], &[("a", "b"), ("", "")][..]),
+            (&[("a", "1"), ("b", "2")][..], &[("b", "2"), ("a", "1")][..]),
+        ];
+        for (left, right) in cases {
+            assert_ne!(
+                form(left).fingerprint(),
+                form(right).fingerprint(),
+                "{left:?} and {right:?}"
+            );
+        }
+        assert_eq!(
+            form(&[("a", "1")]).fingerprint(),
+            form(&[("a", "1")]).fingerprint()
+        );
+    }
+
+    #[test]
+    fn multipart_bodies_ignore_the_boundary() {
+        let upload = |multipart: Multipart| {
+            request("https://example.com/upload")
+                .with_multipart(multipart)
+                .fingerprint()
+        };
+        let first = Multipart::new().text("ab", "c");
+        let second = Multipart::new().text("ab", "c");
+        assert_ne!(first.boundary(), second.boundary());
+        assert_eq!(upload(first), upload(second));
+
+        assert_ne!(
+            upload(Multipart::new().text("ab", "c")),
+            upload(Multipart::new().text("a", "bc"))
+        );
+        assert_ne!(
+            upload(Multipart::new().text("file", "data")),
+            upload(Multipart::new().file("file", "a.txt", "text/plain", "data"))
+        );
+        assert_ne!(
+            upload(Multipart::new().file("file", "a.txt", "text/plain", "data")),
+            upload(Multipart::new().file("file", "a.txt", "text/csv", "data"))
+        );
+    }
+
+    #[test]
+    fn ignored_query_params_collapse_to_one_fingerprint() {
+        let fingerprinter = ConfigurableFingerprinter::new()
+            .with_ignored_query_param("sessionid")
+            .with_ignored_query_param("utm_source");
+        let fingerprints: Vec<String> = [
+            "https://example.com/item?id=1",
+            "https://example.com/item?id=1&sessionid=abc",
+            "https://example.com/item?sessionid=xyz&id=1",
+            "https://example.com/item?id=1&utm_source=mail&sessionid=",
+        ]
+        .iter()
+        .map(|url| fingerprinter.fingerprint(&request(url)))
+        .collect();
+        assert!(
+            fingerprints.iter().all(|f| *f == fingerprints[0]),
+            "{fingerprints:?}"
+        );
+
+        assert_eq!(
+            fingerprinter.fingerprint(&request("https://example.com/item?sessionid=abc")),
+            fingerprinter.fingerprint(&request("https://example.com/item"))
+        );
+        assert_ne!(
+            fingerprinter.fingerprint(&request("https://example.com/item?id=2&sessionid=abc")),
+            fingerprints[0]
+        );
+        assert_ne!(
+            fingerprinter.fingerprint(&request("https://example.com/item?id=1&sessionid2=abc")),
+            fingerprints[0]
+        );
+
+        // Without canonicalization the order of the kept parameters matters.
+        assert_ne!(
+            fingerprinter.fingerprint(&request("https://example.com/item?a=1&b=2")),
+            fingerprinter.fingerprint(&request("https://example.com/item?b=2&a=1"))
+        );
+        let canonical = fingerprinter.with_canonicalization(CanonicalizeOptions::default());
+        assert_eq!(
+            canonical.fingerprint(&request("https://example.com/item?a=1&sessionid=x&b=2#top")),
+            canonical.fingerprint(&request("https://example.com/item?b=2&a=1"))
+        );
+    }
+
+    #[test]
+    fn configured_headers_and_meta_keys() {
+        let fingerprinter = ConfigurableFingerprinter::new()
+            .with_header("Accept-Language")
+            .with_meta_key("page");
+        let with_header = |values: &[&str]| {
+            let mut request = request("https://example.com/");
+            for value in values {
+                request
+                    .headers
+                    .append("accept-language", HeaderValue::from_str(value).unwrap());
+            }
+            fingerprinter.fingerprint(&request)
+        };
+
+        assert_eq!(
+            with_header(&["fr"]),
+            fingerprinter.fingerprint(
+                &request("https://example.com/")
+                    .with_header("ACCEPT-LANGUAGE", "fr")
+                    .unwrap()
+            )
+        );
+        assert_ne!(with_header(&["fr"]), with_header(&["de"]));
+        assert_ne!(with_header(&[]), with_header(&[""]));
+        assert_ne!(with_header(&["ab"]), with_header(&["a", "b"]));
+        assert_ne!(with_header(&["a", "b"]), with_header(&["b", "a"]));
+
+        let with_page = |page: Option<Value>| {
+            let request = request("https://example.com/");
+            let request = match page {
+                Some(page) => request.with_meta("page", page),
+                None => request,
+            };
+            fingerprinter.fingerprint(&request)
+        };
+        assert_eq!(with_page(Some(json!(2))), with_page(Some(json!(2))));
+        assert_ne!(with_page(Some(json!(2))), with_page(Some(json!(3))));
+        assert_ne!(with_page(None), with_page(Some(Value::Null)));
+        assert_eq!(
+            with_page(Some(json!(2))),
+            fingerprinter.fingerprint(
+                &request("https://example.com/")
+                    .with_meta("page", json!(2))
+                    .with_meta("other", json!(true))
+            )
+        );
+    }
+}
@@ -0,0 +1,956 @@
+//! # JSONPath Module
+//!
+//! Queries JSON documents with JSONPath expressions, as standardised by
+//! RFC 9535.
+//!
+//! ## Overview
+//!
+//! `JsonPath::compile` parses an expression once so it can be run against
+//! any number of documents, and `get_cached_json_path` keeps compiled
+//! expressions in a global cache, the way
+//! [`selector_cache`](crate::selector_cache) does for CSS selectors.
+//!
+//! Supported syntax:
+//! - `$` for the root and `@` for the current node in filters
+//! - child segments: `.name`, `.*`, `['name']`, `[0]`, `[-1]`, `[0:10:2]`
+//!   and unions such as `['a','b']`
+//! - descendant segments: `..name`, `..*`, `..[0]`
+//! - filters: `[?@.price < 10 && @.tags]`, with `==`, `!=`, `<`, `<=`, `>`,
+//!   `>=`, `&&`, `||`, `!`, parentheses, and the `length()`, `count()`,
+//!   `match()`, `search()` and `value()` functions
+//!
+//! Expressions are parsed as strictly as the RFC grammar: surrounding
+//! whitespace, indices with leading zeros or beyond ±(2^53 - 1), and `!`
+//! in front of a comparison rather than a parenthesized one are errors.
+//!
+//! `query` returns references into the queried document, while
+//! `Response::json_path` returns owned values.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use spider_util::json_path;
+//!
+//! let titles = response.json_path("$.data.items[*].title")?;
+//!
+//! let value: serde_json::Value = response.json()?;
+//! let cheap = json_path::query(&value, "$..products[?@.price < 10].id")?;
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::error::SpiderError;
+
+/// The largest integer I-JSON numbers represent exactly, the bound of
+/// indices and slice parameters.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+// Global JSONPath cache to avoid repeated compilation
+static JSON_PATH_CACHE: Lazy<RwLock<HashMap<String, Arc<JsonPath>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Get a compiled JSONPath expression from the cache or compile and store it if not present
+pub fn get_cached_json_path(expr: &str) -> Result<Arc<JsonPath>, SpiderError> {
+    {
+        let cache = JSON_PATH_CACHE.read();
+        if let Some(cached) = cache.get(expr) {
+            return Ok(Arc::clone(cached));
+        }
+    }
+
+    let path = Arc::new(JsonPath::compile(expr)?);
+    let mut cache = JSON_PATH_CACHE.write();
+    if let Some(cached) = cache.get(expr) {
+        return Ok(Arc::clone(cached));
+    }
+    cache.insert(expr.to_string(), Arc::clone(&path));
+    Ok(path)
+}
+
+/// Runs a JSONPath query against `value`, compiling it through the cache.
+pub fn query<'a>(value: &'a Value, expr: &str) -> Result<Vec<&'a Value>, SpiderError> {
+    Ok(get_cached_json_path(expr)?.query(value))
+}
+
+/// A compiled JSONPath expression.
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    /// Compiles a JSONPath expression.
+    ///
+    /// Returns [`SpiderError::JsonPathError`] if the expression is invalid.
+    pub fn compile(expr: &str) -> Result<Self, SpiderError> {
+        let mut parser = Parser {
+            input: expr,
+            pos: 0,
+        };
+        if !parser.eat("$") {
+            return Err(parser.error("expected `$`"));
+        }
+        let segments = parser.segments()?;
+        if parser.pos < parser.input.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(Self {
+            source: expr.to_owned(),
+            segments,
+        })
+    }
+
+    /// Returns the expression this was compiled from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the values the expression selects in `value`, in order.
+    pub fn query<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        select(&self.segments, value, value)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug, Clone)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Logical),
+}
+
+#[derive(Debug, Clone)]
+enum Logical {
+    Or(Box<Logical>, Box<Logical>),
+    And(Box<Logical>, Box<Logical>),
+    Not(Box<Logical>),
+    Compare(CompareOp, Comparable, Comparable),
+    /// Whether a query selects anything.
+    Exists(Query),
+    /// A `match()` or `search()` call.
+    Function(Function),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone)]
+enum Comparable {
+    Literal(Value),
+    Query(Query),
+    Function(Function),
+}
+
+#[derive(Debug, Clone)]
+struct Query {
+    /// Whether the query starts at the root (`$`) or the current node (`@`).
+    absolute: bool,
+    segments: Vec<Segment>,
+}
+
+impl Query {
+    /// Whether the query selects at most one node.
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|segment| {
+            matches!(
+                segment,
+                Segment::Child(selectors)
+                    if matches!(selectors.as_slice(), [Selector::Name(_) | Selector::Index(_)])
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Function {
+    Length(Box<Comparable>),
+    Count(Query),
+    Value(Query),
+    /// The pattern is compiled up front when it is a literal.
+    Match(Box<Comparable>, Box<Comparable>, Option<Regex>),
+    Search(Box<Comparable>, Box<Comparable>, Option<Regex>),
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> SpiderError {
+        SpiderError::JsonPathError(format!(
+            "{message} at position {} in '{}'",
+            self.pos, self.input
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.input[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), SpiderError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{token}`")))
+        }
+    }
+
+    fn skip_blank(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn segments(&mut self) -> Result<Vec<Segment>, SpiderError> {
+        let mut segments = Vec::new();
+        loop {
+            let start = self.pos;
+            self.skip_blank();
+            if self.eat("..") {
+                let selectors = if self.peek() == Some('[') {
+                    self.bracketed()?
+                } else if self.eat("*") {
+                    vec![Selector::Wildcard]
+                } else {
+                    vec![Selector::Name(self.member_name()?)]
+                };
+                segments.push(Segment::Descendant(selectors));
+            } else if self.eat(".") {
+                let selector = if self.eat("*") {
+                    Selector::Wildcard
+                } else {
+                    Selector::Name(self.member_name()?)
+                };
+                segments.push(Segment::Child(vec![selector]));
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.bracketed()?));
+            } else {
+                self.pos = start;
+                return Ok(segments);
+            }
+        }
+    }
+
+    fn member_name(&mut self) -> Result<String, SpiderError> {
+        let rest = &self.input[self.pos..];
+        let starts_name = rest
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_' || !c.is_ascii());
+        if !starts_name {
+            return Err(self.error("expected a member name"));
+        }
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || !c.is_ascii()))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        Ok(rest[..len].to_owned())
+    }
+
+    fn bracketed(&mut self) -> Result<Vec<Selector>, SpiderError> {
+        self.expect("[")?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_blank();
+            selectors.push(self.selector()?);
+            self.skip_blank();
+            if !self.eat(",") {
+                self.expect("]")?;
+                return Ok(selectors);
+            }
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, SpiderError> {
+        match self.peek() {
+            Some(quote @ ('\'' | '"')) => Ok(Selector::Name(self.string(quote)?)),
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.pos += 1;
+                Ok(Selector::Filter(self.logical_or()?))
+            }
+            _ => {
+                let start = self.integer()?;
+                self.skip_blank();
+                if !self.eat(":") {
+                    return start
+                        .map(Selector::Index)
+                        .ok_or_else(|| self.error("expected a selector"));
+                }
+                self.skip_blank();
+                let end = self.integer()?;
+                self.skip_blank();
+                let step = if self.eat(":") {
+                    self.skip_blank();
+                    self.integer()?
+                } else {
+                    None
+                };
+                Ok(Selector::Slice(start, end, step))
+            }
+        }
+    }
+
+    /// Parses an index or slice bound: `0`, or an optionally negative
+    /// integer without leading zeros, within the I-JSON range.
+    fn integer(&mut self) -> Result<Option<i64>, SpiderError> {
+        let rest = &self.input[self.pos..];
+        let digits = rest.strip_prefix('-').unwrap_or(rest);
+        let len = digits
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(digits.len());
+        if len == 0 {
+            return Ok(None);
+        }
+        if digits.starts_with('0') && (len > 1 || digits.len() < rest.len()) {
+            return Err(self.error("integers cannot have leading zeros or be `-0`"));
+        }
+        let len = len + rest.len() - digits.len();
+        let value = rest[..len]
+            .parse::<i64>()
+            .ok()
+            .filter(|value| value.abs() <= MAX_SAFE_INTEGER)
+            .ok_or_else(|| self.error("integer out of range"))?;
+        self.pos += len;
+        Ok(Some(value))
+    }
+
+    fn string(&mut self, quote: char) -> Result<String, SpiderError> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '\\' => {
+                    let Some(escaped) = self.peek() else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.pos += escaped.len_utf8();
+                    out.push(match escaped {
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape()?,
+                        '/' | '\\' | '\'' | '"' => escaped,
+                        _ => return Err(self.error("invalid escape")),
+                    });
+                }
+                c if c == quote => return Ok(out),
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, SpiderError> {
+        let high = self.hex4()?;
+        if (0xD800..0xDC00).contains(&high) {
+            self.expect("\\u")?;
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("invalid surrogate pair"));
+            }
+            let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+            return char::from_u32(code).ok_or_else(|| self.error("invalid escape"));
+        }
+        char::from_u32(high).ok_or_else(|| self.error("invalid escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, SpiderError> {
+        let hex = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("invalid escape"))?;
+        let code = u32::from_str_radix(hex, 16).map_err(|_| self.error("invalid escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn logical_or(&mut self) -> Result<Logical, SpiderError> {
+        let mut expr = self.logical_and()?;
+        loop {
+            self.skip_blank();
+            if !self.eat("||") {
+                return Ok(expr);
+            }
+            expr = Logical::Or(Box::new(expr), Box::new(self.logical_and()?));
+        }
+    }
+
+    fn logical_and(&mut self) -> Result<Logical, SpiderError> {
+        let mut expr = self.basic()?;
+        loop {
+            self.skip_blank();
+            if !self.eat("&&") {
+                return Ok(expr);
+            }
+            expr = Logical::And(Box::new(expr), Box::new(self.basic()?));
+        }
+    }
+
+    fn basic(&mut self) -> Result<Logical, SpiderError> {
+        self.skip_blank();
+        // `!` negates a test or a parenthesized expression, not a comparison.
+        if self.eat("!") {
+            self.skip_blank();
+            let operand = if self.peek() == Some('(') {
+                self.paren()?
+            } else {
+                let operand = self.comparable()?;
+                self.test(operand)?
+            };
+            return Ok(Logical::Not(Box::new(operand)));
+        }
+        if self.peek() == Some('(') {
+            return self.paren();
+        }
+
+        let left = self.comparable()?;
+        self.skip_blank();
+        let op = if self.eat("==") {
+            CompareOp::Equal
+        } else if self.eat("!=") {
+            CompareOp::NotEqual
+        } else if self.eat("<=") {
+            CompareOp::LessEqual
+        } else if self.eat(">=") {
+            CompareOp::GreaterEqual
+        } else if self.eat("<") {
+            CompareOp::Less
+        } else if self.eat(">") {
+            CompareOp::Greater
+        } else {
+            return self.test(left);
+        };
+
+        self.skip_blank();
+        let right = self.comparable()?;
+        self.check_value(&left)?;
+        self.check_value(&right)?;
+        Ok(Logical::Compare(op, left, right))
+    }
+
+    fn paren(&mut self) -> Result<Logical, SpiderError> {
+        self.expect("(")?;
+        let expr = self.logical_or()?;
+        self.skip_blank();
+        self.expect(")")?;
+        Ok(expr)
+    }
+
+    /// Turns an operand that is not compared into an existence test or a
+    /// `match()` / `search()` call.
+    fn test(&self, operand: Comparable) -> Result<Logical, SpiderError> {
+        match operand {
+            Comparable::Query(query) => Ok(Logical::Exists(query)),
+            Comparable::Function(function @ (Function::Match(..) | Function::Search(..))) => {
+                Ok(Logical::Function(function))
+            }
+            _ => Err(self.error("expected a comparison")),
+        }
+    }
+
+    /// Checks that an operand yields a single value: a literal, a singular
+    /// query or a function returning a value.
+    fn check_value(&self, comparable: &Comparable) -> Result<(), SpiderError> {
+        match comparable {
+            Comparable::Query(query) if !query.is_singular() => {
+                Err(self.error("expected a singular query"))
+            }
+            Comparable::Function(Function::Match(..) | Function::Search(..)) => {
+                Err(self.error("match() and search() cannot be compared"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn comparable(&mut self) -> Result<Comparable, SpiderError> {
+        match self.peek() {
+            Some(c @ ('$' | '@')) => {
+                self.pos += 1;
+                Ok(Comparable::Query(Query {
+                    absolute: c == '$',
+                    segments: self.segments()?,
+                }))
+            }
+            Some(quote @ ('\'' | '"')) => {
+                Ok(Comparable::Literal(Value::String(self.string(quote)?)))
+            }
+            Some('-' | '0'..='9') => Ok(Comparable::Literal(self.number()?)),
+            Some(c) if c.is_ascii_lowercase() => {
+                let rest = &self.input[self.pos..];
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'))
+                    .unwrap_or(rest.len());
+                let name = &rest[..len];
+                self.pos += len;
+                match name {
+                    "true" => Ok(Comparable::Literal(Value::Bool(true))),
+                    "false" => Ok(Comparable::Literal(Value::Bool(false))),
+                    "null" => Ok(Comparable::Literal(Value::Null)),
+                    _ => self.function(name).map(Comparable::Function),
+                }
+            }
+            _ => Err(self.error("expected a query, literal or function")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, SpiderError> {
+        let rest = &self.input[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(rest.len());
+        let number = serde_json::from_str::<serde_json::Number>(&rest[..len])
+            .map_err(|_| self.error("invalid number"))?;
+        self.pos += len;
+        Ok(Value::Number(number))
+    }
+
+    fn function(&mut self, name: &str) -> Result<Function, SpiderError> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        self.skip_blank();
+        if !self.eat(")") {
+            loop {
+                self.skip_blank();
+                args.push(self.comparable()?);
+                self.skip_blank();
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        let arity = match name {
+            "length" | "count" | "value" => 1,
+            "match" | "search" => 2,
+            _ => return Err(self.error(&format!("unknown function {name}()"))),
+        };
+        if args.len() != arity {
+            return Err(self.error(&format!("{name}() takes {arity} argument(s)")));
+        }
+
+        let mut args = args.into_iter();
+        let first = args.next().expect("arity checked");
+        match name {
+            "length" => {
+                self.check_value(&first)?;
+                Ok(Function::Length(Box::new(first)))
+            }
+            "count" | "value" => match first {
+                Comparable::Query(query) if name == "count" => Ok(Function::Count(query)),
+                Comparable::Query(query) => Ok(Function::Value(query)),
+                _ => Err(self.error(&format!("{name}() takes a query"))),
+            },
+            _ => {
+                let pattern = args.next().expect("arity checked");
+                self.check_value(&first)?;
+                self.check_value(&pattern)?;
+                let anchored = name == "match";
+                let regex = match &pattern {
+                    Comparable::Literal(Value::String(pattern)) => Some(
+                        compile_regex(pattern, anchored)
+                            .ok_or_else(|| self.error("invalid regular expression"))?,
+                    ),
+                    _ => None,
+                };
+                let (first, pattern) = (Box::new(first), Box::new(pattern));
+                Ok(if anchored {
+                    Function::Match(first, pattern, regex)
+                } else {
+                    Function::Search(first, pattern, regex)
+                })
+            }
+        }
+    }
+}
+
+fn compile_regex(pattern: &str, anchored: bool) -> Option<Regex> {
+    if anchored {
+        Regex::new(&format!("^(?:{pattern})$")).ok()
+    } else {
+        Regex::new(pattern).ok()
+    }
+}
+
+fn select<'a>(segments: &[Segment], root: &'a Value, current: &'a Value) -> Vec<&'a Value> {
+    let mut nodes = vec![current];
+    for segment in segments {
+        let mut selected = Vec::new();
+        for node in nodes {
+            match segment {
+                Segment::Child(selectors) => {
+                    for selector in selectors {
+                        apply(selector, node, root, &mut selected);
+                    }
+                }
+                Segment::Descendant(selectors) => {
+                    let mut descendants = Vec::new();
+                    collect_descendants(node, &mut descendants);
+                    for descendant in descendants {
+                        for selector in selectors {
+                            apply(selector, descendant, root, &mut selected);
+                        }
+                    }
+                }
+            }
+        }
+        nodes = selected;
+    }
+    nodes
+}
+
+/// Collects `value` and every value nested in it, in document order.
+fn collect_descendants<'a>(value: &'a Value, out: &mut Vec<&'a Value>) {
+    out.push(value);
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| collect_descendants(item, out)),
+        Value::Object(map) => map.values().for_each(|item| collect_descendants(item, out)),
+        _ => {}
+    }
+}
+
+fn children(value: &Value) -> Box<dyn Iterator<Item = &Value> + '_> {
+    match value {
+        Value::Array(items) => Box::new(items.iter()),
+        Value::Object(map) => Box::new(map.values()),
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+fn apply<'a>(selector: &Selector, node: &'a Value, root: &'a Value, out: &mut Vec<&'a Value>) {
+    match selector {
+        Selector::Name(name) => out.extend(node.as_object().and_then(|map| map.get(name))),
+        Selector::Wildcard => out.extend(children(node)),
+        Selector::Index(index) => {
+            if let Value::Array(items) = node
+                && let Some(index) = normalize_index(*index, items.len())
+            {
+                out.extend(items.get(index));
+            }
+        }
+        Selector::Slice(start, end, step) => {
+            if let Value::Array(items) = node {
+                slice(items, *start, *end, *step, out);
+            }
+        }
+        Selector::Filter(filter) => {
+            out.extend(children(node).filter(|child| test(filter, child, root)));
+        }
+    }
+}
+
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    if index >= 0 {
+        usize::try_from(index).ok()
+    } else {
+        len.checked_sub(usize::try_from(index.unsigned_abs()).ok()?)
+    }
+}
+
+fn slice<'a>(
+    items: &'a [Value],
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    out: &mut Vec<&'a Value>,
+) {
+    let len = items.len() as i64;
+    let step = step.unwrap_or(1);
+    let normalize = |index: i64| if index >= 0 { index } else { len + index };
+
+    if step > 0 {
+        let lower = start.map_or(0, normalize).clamp(0, len);
+        let upper = end.map_or(len, normalize).clamp(0, len);
+        let mut index = lower;
+        while index < upper {
+            out.push(&items[index as usize]);
+            let Some(next) = index.checked_add(step) else {
+                break;
+            };
+            index = next;
+        }
+    } else if step < 0 {
+        let upper = start.map_or(len - 1, normalize).clamp(-1, len - 1);
+        let lower = end.map_or(-1, normalize).clamp(-1, len - 1);
+        let mut index = upper;
+        while lower < index {
+            out.push(&items[index as usize]);
+            let Some(next) = index.checked_add(step) else {
+                break;
+            };
+            index = next;
+        }
+    }
+}
+
+fn test(expr: &Logical, current: &Value, root: &Value) -> bool {
+    match expr {
+        Logical::Or(left, right) => test(left, current, root) || test(right, current, root),
+        Logical::And(left, right) => test(left, current, root) && test(right, current, root),
+        Logical::Not(expr) => !test(expr, current, root),
+        Logical::Compare(op, left, right) => {
+            let left = evaluate(left, current, root);
+            let right = evaluate(right, current, root);
+            compare(*op, left.as_deref(), right.as_deref())
+        }
+        Logical::Exists(query) => !run(query, current, root).is_empty(),
+        Logical::Function(function) => match function {
+            Function::Match(value, pattern, regex) | Function::Search(value, pattern, regex) => {
+                let anchored = matches!(function, Function::Match(..));
+                let value = evaluate(value, current, root);
+                let Some(Value::String(text)) = value.as_deref() else {
+                    return false;
+                };
+                match regex {
+                    Some(regex) => regex.is_match(text),
+                    None => match evaluate(pattern, current, root).as_deref() {
+                        Some(Value::String(pattern)) => compile_regex(pattern, anchored)
+                            .is_some_and(|regex| regex.is_match(text)),
+                        _ => false,
+                    },
+                }
+            }
+            _ => false,
+        },
+    }
+}
+
+fn run<'a>(query: &Query, current: &'a Value, root: &'a Value) -> Vec<&'a Value> {
+    let start = if query.absolute { root } else { current };
+    select(&query.segments, root, start)
+}
+
+/// Evaluates an operand, `None` standing for the absence of a value.
+fn evaluate<'a>(
+    comparable: &'a Comparable,
+    current: &'a Value,
+    root: &'a Value,
+) -> Option<Cow<'a, Value>> {
+    match comparable {
+        Comparable::Literal(value) => Some(Cow::Borrowed(value)),
+        Comparable::Query(query) => single(run(query, current, root)).map(Cow::Borrowed),
+        Comparable::Function(Function::Length(value)) => {
+            let length = match evaluate(value, current, root)?.as_ref() {
+                Value::String(text) => text.chars().count(),
+                Value::Array(items) => items.len(),
+                Value::Object(map) => map.len(),
+                _ => return None,
+            };
+            Some(Cow::Owned(Value::from(length)))
+        }
+        Comparable::Function(Function::Count(query)) => {
+            Some(Cow::Owned(Value::from(run(query, current, root).len())))
+        }
+        Comparable::Function(Function::Value(query)) => {
+            single(run(query, current, root)).map(Cow::Borrowed)
+        }
+        Comparable::Function(Function::Match(..) | Function::Search(..)) => None,
+    }
+}
+
+fn single(nodes: Vec<&Value>) -> Option<&Value> {
+    match nodes.as_slice() {
+        [value] => Some(value),
+        _ => None,
+    }
+}
+
+fn compare(op: CompareOp, left: Option<&Value>, right: Option<&Value>) -> bool {
+    match op {
+        CompareOp::Equal => equal(left, right),
+        CompareOp::NotEqual => !equal(left, right),
+        CompareOp::Less => less(left, right),
+        CompareOp::LessEqual => less(left, right) || equal(left, right),
+        CompareOp::Greater => less(right, left),
+        CompareOp::GreaterEqual => less(right, left) || equal(left, right),
+    }
+}
+
+fn equal(left: Option<&Value>, right: Option<&Value>) -> bool {
+    match (left, right) {
+        (None, None) => true,
+        (Some(left), Some(right)) => values_equal(left, right),
+        _ => false,
+    }
+}
+
+/// Deep equality, comparing numbers by value so that `1 == 1.0`.
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => match (left.as_i64(), right.as_i64()) {
+            (Some(left), Some(right)) => left == right,
+            _ => matches!((left.as_f64(), right.as_f64()), (Some(l), Some(r)) if l == r),
+        },
+        (Value::Array(left), Value::Array(right)) => {
+            left.len() == right.len() && left.iter().zip(right).all(|(l, r)| values_equal(l, r))
+        }
+        (Value::Object(left), Value::Object(right)) => {
+            left.len() == right.len()
+                && left.iter().all(|(key, value)| {
+                    right
+                        .get(key)
+                        .is_some_and(|other| values_equal(value, other))
+                })
+        }
+        _ => left == right,
+    }
+}
+
+fn less(left: Option<&Value>, right: Option<&Value>) -> bool {
+    match (left, right) {
+        (Some(Value::Number(left)), Some(Value::Number(right))) => {
+            match (left.as_i64(), right.as_i64()) {
+                (Some(left), Some(right)) => left < right,
+                _ => matches!((left.as_f64(), right.as_f64()), (Some(l), Some(r)) if l < r),
+            }
+        }
+        (Some(Value::String(left)), Some(Value::String(right))) => left < right,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Runs a query and returns the selected values as an array.
+    fn run(value: &Value, expr: &str) -> Value {
+        Value::Array(query(value, expr).unwrap().into_iter().cloned().collect())
+    }
+
+    fn count(value: &Value, expr: &str) -> usize {
+        query(value, expr).unwrap().len()
+    }
+
+    #[test]
+    fn slices() {
+        let value = json!(["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(run(&value, "$[1:3]"), json!(["b", "c"]));
+        assert_eq!(run(&value, "$[5:]"), json!(["f", "g"]));
+        assert_eq!(run(&value, "$[1:5:2]"), json!(["b", "d"]));
+        assert_eq!(run(&value, "$[-2:]"), json!(["f", "g"]));
+    }
+
+    #[test]
+    fn slices_with_negative_steps() {
+        let value = json!(["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(run(&value, "$[5:1:-2]"), json!(["f", "d"]));
+        assert_eq!(
+            run(&value, "$[::-1]"),
+            json!(["g", "f", "e", "d", "c", "b", "a"])
+        );
+        assert_eq!(run(&value, "$[-1:-3:-1]"), json!(["g", "f"]));
+        assert_eq!(run(&value, "$[1:5:-1]"), json!([]));
+    }
+
+    #[test]
+    fn slices_with_out_of_range_bounds() {
+        let value = json!(["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(count(&value, "$[-100:100]"), 7);
+        assert_eq!(run(&value, "$[10:]"), json!([]));
+        assert_eq!(run(&value, "$[:-100]"), json!([]));
+        assert_eq!(run(&value, "$[100:-100:-3]"), json!(["g", "d", "a"]));
+        assert_eq!(run(&value, "$[2:1]"), json!([]));
+        assert_eq!(run(&value, "$[::0]"), json!([]));
+        assert_eq!(run(&json!({"a": 1}), "$[0:2]"), json!([]));
+    }
+
+    #[test]
+    fn filters() {
+        let value = json!({
+            "a": [3, 5, 1, 2, 4, 6, {"b": "j"}, {"b": "k"}, {"b": {}}, {"b": "kilo"}],
+            "o": {"p": 1, "q": 2, "r": 3, "s": 5, "t": {"u": 6}},
+            "e": "f"
+        });
+        assert_eq!(run(&value, "$.a[?@.b == 'kilo']"), json!([{"b": "kilo"}]));
+        assert_eq!(run(&value, "$.a[?(@.b == 'kilo')]"), json!([{"b": "kilo"}]));
+        assert_eq!(run(&value, "$.a[?@>3.5]"), json!([5, 4, 6]));
+        assert_eq!(count(&value, "$.a[?@.b]"), 4);
+        assert_eq!(run(&value, "$.o[?@<3, ?@<3]"), json!([1, 2, 1, 2]));
+        assert_eq!(
+            run(&value, "$.a[?@<2 || @.b == \"k\"]"),
+            json!([1, {"b": "k"}])
+        );
+        assert_eq!(run(&value, "$.o[?@>1 && @<4]"), json!([2, 3]));
+        assert_eq!(run(&value, "$.o[?@.u || @.x]"), json!([{"u": 6}]));
+        assert_eq!(
+            run(&value, "$.a[?match(@.b, '[jk]')]"),
+            json!([{"b": "j"}, {"b": "k"}])
+        );
+        assert_eq!(count(&value, "$.a[?search(@.b, '[jk]')]"), 3);
+        assert_eq!(count(&value, "$.a[?@.b == $.x]"), 6);
+        assert_eq!(count(&value, "$.a[?@ == @]"), 10);
+        assert_eq!(count(&value, "$.a[?!@.b]"), 6);
+        assert_eq!(count(&value, "$.a[?!(@.b == 'j')]"), 9);
+        assert_eq!(
+            run(&value, "$.a[?length(@.b) == 4]"),
+            json!([{"b": "kilo"}])
+        );
+        assert_eq!(count(&value, "$[?count(@.*) > 1]"), 2);
+    }
+
+    #[test]
+    fn rejects_syntax_outside_the_standard() {
+        for expr in [
+            " $.a",
+            "$.a ",
+            "$.a[?!@.b == 1]",
+            "$.a[?!!@.b]",
+            "$.a[?@.* == 1]",
+            "$.a[?1]",
+            "$[01]",
+            "$[-0]",
+            "$[0:-0]",
+            "$[9007199254740992]",
+        ] {
+            assert!(
+                JsonPath::compile(expr).is_err(),
+                "{expr} should be rejected"
+            );
+        }
+        for expr in ["$[0]", "$[-1]", "$[0:-1:2]", "$[9007199254740991]", "$ .a"] {
+            assert!(JsonPath::compile(expr).is_ok(), "{expr} should be accepted");
+        }
+    }
+}
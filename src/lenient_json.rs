@@ -0,0 +1,453 @@
+//! # Lenient JSON Module
+//!
+//! A forgiving JSON parser for data embedded in web pages.
+//!
+//! ## Overview
+//!
+//! JSON found in `<script>` tags is often not quite JSON: it may carry
+//! trailing commas, comments, single-quoted strings, unquoted keys, raw
+//! newlines inside strings or JavaScript values such as `undefined`. The
+//! functions of this module accept all of these, mapping `undefined`,
+//! `void 0`, `NaN` and `Infinity` to `null`. Valid JSON is parsed by
+//! `serde_json` directly.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use spider_util::lenient_json;
+//!
+//! let value = lenient_json::from_str("{name: 'Widget', tags: ['a', 'b',], price: undefined}")?;
+//! assert_eq!(value["name"], "Widget");
+//! ```
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use serde_json::{Map, Number, Value};
+
+use crate::error::SpiderError;
+
+/// How deeply arrays and objects may be nested.
+const MAX_DEPTH: usize = 256;
+
+/// Parses a JSON or JavaScript literal value.
+///
+/// Surrounding whitespace, comments and a trailing `;` are ignored; anything
+/// else after the value is an error.
+pub fn from_str(input: &str) -> Result<Value, SpiderError> {
+    if let Ok(value) = serde_json::from_str(input) {
+        return Ok(value);
+    }
+
+    let mut parser = Parser::new(input);
+    let value = parser.value(0)?;
+    parser.skip_trivia();
+    if parser.peek() == Some(b';') {
+        parser.pos += 1;
+        parser.skip_trivia();
+    }
+    if parser.pos < input.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Parses the value at the start of `input`, ignoring whatever follows it.
+///
+/// Returns the value and the number of bytes it spans, leading whitespace and
+/// comments included.
+pub fn from_str_prefix(input: &str) -> Result<(Value, usize), SpiderError> {
+    from_str_prefix_with(input, &HashMap::new())
+}
+
+/// Like [`from_str_prefix`], with identifiers in `bindings` standing for
+/// their values, as the parameters of a function do in its body.
+pub(crate) fn from_str_prefix_with(
+    input: &str,
+    bindings: &HashMap<&str, Value>,
+) -> Result<(Value, usize), SpiderError> {
+    let mut parser = Parser::new(input);
+    parser.bindings = bindings;
+    let value = parser.value(0)?;
+    Ok((value, parser.pos))
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    bindings: &'a HashMap<&'a str, Value>,
+}
+
+static NO_BINDINGS: Lazy<HashMap<&str, Value>> = Lazy::new(HashMap::new);
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            bindings: &NO_BINDINGS,
+        }
+    }
+
+    fn error(&self, message: &str) -> SpiderError {
+        SpiderError::JsonError(format!("{message} at byte {}", self.pos))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                self.pos += comment.find("*/").map_or(trimmed.len(), |end| end + 4);
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, SpiderError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+
+        self.skip_trivia();
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(quote @ (b'"' | b'\'' | b'`')) => self.string(quote).map(Value::String),
+            Some(b'-' | b'+' | b'.' | b'0'..=b'9') => self.number(),
+            Some(_) => match self.identifier() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                "null" | "undefined" | "NaN" | "Infinity" => Ok(Value::Null),
+                // `void 0` is a common minified spelling of `undefined`.
+                "void" => {
+                    self.value(depth + 1)?;
+                    Ok(Value::Null)
+                }
+                "" => Err(self.error("expected a value")),
+                name => match self.bindings.get(name) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(self.error("unexpected identifier")),
+                },
+            },
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, SpiderError> {
+        self.pos += 1;
+        let mut map = Map::new();
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(map));
+                }
+                Some(quote @ (b'"' | b'\'' | b'`')) => {
+                    let key = self.string(quote)?;
+                    map.insert(key, self.member_value(depth)?);
+                }
+                Some(b'0'..=b'9' | b'-' | b'.') => {
+                    let key = self.number()?.to_string();
+                    map.insert(key, self.member_value(depth)?);
+                }
+                Some(_) => {
+                    let key = self.identifier();
+                    if key.is_empty() {
+                        return Err(self.error("expected an object key"));
+                    }
+                    map.insert(key.to_owned(), self.member_value(depth)?);
+                }
+                None => return Err(self.error("unterminated object")),
+            }
+
+            self.skip_trivia();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {}
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn member_value(&mut self, depth: usize) -> Result<Value, SpiderError> {
+        self.skip_trivia();
+        if self.peek() != Some(b':') {
+            return Err(self.error("expected `:`"));
+        }
+        self.pos += 1;
+        self.value(depth + 1)
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, SpiderError> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                // Holes, as in `[1,,2]`, read as `null`.
+                Some(b',') => {
+                    self.pos += 1;
+                    items.push(Value::Null);
+                    continue;
+                }
+                Some(_) => items.push(self.value(depth + 1)?),
+                None => return Err(self.error("unterminated array")),
+            }
+
+            self.skip_trivia();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {}
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn string(&mut self, quote: u8) -> Result<String, SpiderError> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let rest = self.rest();
+            let Some(end) = rest.find(|c: char| c as u32 == quote as u32 || c == '\\') else {
+                return Err(self.error("unterminated string"));
+            };
+            out.push_str(&rest[..end]);
+            self.pos += end;
+
+            if self.peek() == Some(quote) {
+                self.pos += 1;
+                return Ok(out);
+            }
+
+            self.pos += 1;
+            let Some(escaped) = self.rest().chars().next() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += escaped.len_utf8();
+            match escaped {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                'b' => out.push('\u{8}'),
+                'f' => out.push('\u{c}'),
+                'v' => out.push('\u{b}'),
+                '0' => out.push('\0'),
+                'x' => out.push(self.hex_char(2)?),
+                'u' => out.push(self.unicode_escape()?),
+                // A backslash before a line break continues the string.
+                '\n' => {}
+                '\r' => {
+                    if self.peek() == Some(b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                other => out.push(other),
+            }
+        }
+    }
+
+    fn hex_char(&mut self, digits: usize) -> Result<char, SpiderError> {
+        let code = self.hex(digits)?;
+        char::from_u32(code).ok_or_else(|| self.error("invalid escape"))
+    }
+
+    fn hex(&mut self, digits: usize) -> Result<u32, SpiderError> {
+        let hex = self
+            .input
+            .get(self.pos..self.pos + digits)
+            .ok_or_else(|| self.error("invalid escape"))?;
+        let code = u32::from_str_radix(hex, 16).map_err(|_| self.error("invalid escape"))?;
+        self.pos += digits;
+        Ok(code)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, SpiderError> {
+        if self.peek() == Some(b'{') {
+            let rest = self.rest();
+            let end = rest.find('}').ok_or_else(|| self.error("invalid escape"))?;
+            self.pos += 1;
+            let c = self.hex_char(end - 1)?;
+            self.pos += 1;
+            return Ok(c);
+        }
+
+        let high = self.hex(4)?;
+        if (0xD800..0xDC00).contains(&high) && self.rest().starts_with("\\u") {
+            let start = self.pos;
+            self.pos += 2;
+            let low = self.hex(4)?;
+            if (0xDC00..0xE000).contains(&low) {
+                let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                return char::from_u32(code).ok_or_else(|| self.error("invalid escape"));
+            }
+            self.pos = start;
+        }
+        Ok(char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    fn number(&mut self) -> Result<Value, SpiderError> {
+        let start = self.pos;
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-' | '_')))
+            .unwrap_or(rest.len());
+        self.pos += len;
+
+        let text = rest[..len].replace('_', "");
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(&text)),
+        };
+
+        if digits == "Infinity" || digits == "NaN" {
+            return Ok(Value::Null);
+        }
+        let radix = match digits.get(..2) {
+            Some("0x" | "0X") => Some(16),
+            Some("0o" | "0O") => Some(8),
+            Some("0b" | "0B") => Some(2),
+            _ => None,
+        };
+        if let Some(radix) = radix {
+            let value = i64::from_str_radix(&digits[2..], radix).map_err(|_| {
+                self.pos = start;
+                self.error("invalid number")
+            })?;
+            return Ok(Value::from(if negative { -value } else { value }));
+        }
+
+        let signed = if negative {
+            format!("-{digits}")
+        } else {
+            digits.to_owned()
+        };
+        if let Ok(value) = signed.parse::<i64>() {
+            return Ok(Value::from(value));
+        }
+        if let Ok(value) = signed.parse::<u64>() {
+            return Ok(Value::from(value));
+        }
+        match signed.parse::<f64>() {
+            Ok(value) => Ok(Number::from_f64(value).map_or(Value::Null, Value::Number)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("invalid number"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn javascript_literals() {
+        let cases = [
+            ("{\"a\": [1, 2]}", json!({ "a": [1, 2] })),
+            (
+                "{name: 'Widget', tags: ['a', 'b',], price: undefined,}",
+                json!({ "name": "Widget", "tags": ["a", "b"], "price": null }),
+            ),
+            (
+                "/* state */ {a: 1, // count\n b: void 0};",
+                json!({ "a": 1, "b": null }),
+            ),
+            (
+                "[NaN, Infinity, -Infinity, 0x1F, 0b101, 0o17, 1_000, .5, +2]",
+                json!([null, null, null, 31, 5, 15, 1000, 0.5, 2]),
+            ),
+            ("[1,,2]", json!([1, null, 2])),
+            ("{1: 'one', $id: `x`}", json!({ "1": "one", "$id": "x" })),
+            (
+                r"'\x41B\u{43} 😀 line\
+break'",
+                json!("ABC \u{1F600} linebreak"),
+            ),
+            ("\"raw\nnewline\"", json!("raw\nnewline")),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(from_str(input).unwrap(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn prefix_reports_its_length() {
+        let (value, len) = from_str_prefix("  {a: [1]} ; rest").unwrap();
+        assert_eq!(value, json!({ "a": [1] }));
+        assert_eq!(len, "  {a: [1]}".len());
+    }
+
+    #[test]
+    fn bindings_stand_for_their_values() {
+        let bindings = HashMap::from([("a", json!("x"))]);
+        let (value, _) = from_str_prefix_with("{key: a}", &bindings).unwrap();
+        assert_eq!(value, json!({ "key": "x" }));
+        assert!(from_str("{key: a}").is_err());
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        let cases = [
+            "",
+            "   ",
+            "{a: 1} trailing",
+            "[1 2]",
+            "{a 1}",
+            "{: 1}",
+            "'unterminated",
+            "'\\",
+            "'\\uZZZZ'",
+            "'\\u{110000}'",
+            "'\\u{}'",
+            "'\\x4'",
+            "0xZZ",
+            "-",
+            "1.2.3",
+            "identifier",
+        ];
+        for input in cases {
+            assert!(from_str(input).is_err(), "{input:?}");
+        }
+        assert!(from_str(&"[".repeat(MAX_DEPTH + 10)).is_err());
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let input = r#"{a: [1, 'two', {b: "three!"}], /* c */ c: void 0, d: -1.5e3}"#;
+        assert!(from_str(input).is_ok());
+        for end in (0..input.len()).filter(|&end| input.is_char_boundary(end)) {
+            assert!(from_str(&input[..end]).is_err(), "{:?}", &input[..end]);
+        }
+    }
+}
@@ -19,10 +19,23 @@
 //! ```
 
 pub mod bloom_filter;
+pub mod embedded_json;
 pub mod error;
+pub mod feed;
+pub mod fingerprint;
 pub mod item;
+pub mod json_path;
+pub mod lenient_json;
+pub mod link_extractor;
+pub mod meta;
 pub mod metrics;
 pub mod request;
 pub mod response;
+pub mod robots;
+pub mod selector;
 pub mod selector_cache;
+pub mod sitemap;
+pub mod structured_data;
+pub mod table;
 pub mod utils;
+pub mod xpath;
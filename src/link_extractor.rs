@@ -0,0 +1,925 @@
+//! # Link Extractor Module
+//!
+//! Provides a configurable extractor for links found in HTML responses.
+//!
+//! ## Overview
+//!
+//! `LinkExtractor` walks a parsed document in order and collects the URLs
+//! found in a configurable set of tag/attribute pairs (and, optionally, in
+//! plain text), then filters them by URL patterns, domains, site, link type
+//! and file extension. The result is a `Vec<Link>` in document order with
+//! duplicates removed. `Response::links` is a preset built on top of it.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use spider_util::link_extractor::{LinkExtractor, IGNORED_EXTENSIONS};
+//! use spider_util::response::LinkType;
+//!
+//! let extractor = LinkExtractor::new()
+//!     .allow(r"/articles/\d+")?
+//!     .deny(r"\?print=1")?
+//!     .link_types([LinkType::Page])
+//!     .deny_extensions(IGNORED_EXTENSIONS.iter().copied());
+//!
+//! for link in extractor.extract(&response) {
+//!     println!("{}", link.url);
+//! }
+//! ```
+
+use std::collections::HashSet;
+
+use linkify::{LinkFinder, LinkKind};
+use regex::Regex;
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Node};
+use url::Url;
+
+use crate::error::SpiderError;
+use crate::response::{Link, LinkSource, LinkType, Response, document_base_url};
+use crate::robots;
+use crate::utils::{self, CanonicalizeOptions, canonicalize_url};
+
+/// Tag/attribute pairs scanned by default.
+///
+/// `srcset` attributes yield every candidate URL, and `meta[content]` is only
+/// read on `<meta http-equiv="refresh">`.
+pub const DEFAULT_SOURCES: &[(&str, &str)] = &[
+    ("a", "href"),
+    ("area", "href"),
+    ("link", "href"),
+    ("script", "src"),
+    ("img", "src"),
+    ("img", "srcset"),
+    ("audio", "src"),
+    ("video", "src"),
+    ("video", "poster"),
+    ("source", "src"),
+    ("source", "srcset"),
+    ("iframe", "src"),
+    ("frame", "src"),
+    ("form", "action"),
+    ("meta", "content"),
+    ("object", "data"),
+    ("embed", "src"),
+];
+
+/// File extensions that are usually not worth crawling as pages.
+#[rustfmt::skip]
+pub const IGNORED_EXTENSIONS: &[&str] = &[
+    // archives
+    "7z", "7zip", "bz2", "rar", "tar", "tar.gz", "xz", "zip", "gz",
+    // images
+    "mng", "pct", "bmp", "gif", "jpg", "jpeg", "png", "pst", "psp", "tif", "tiff", "ai", "drw",
+    "dxf", "eps", "ps", "svg", "cdr", "ico", "webp",
+    // audio
+    "mp3", "wma", "ogg", "wav", "ra", "aac", "mid", "au", "aiff",
+    // video
+    "3gp", "asf", "asx", "avi", "mov", "mp4", "mpg", "qt", "rm", "swf", "wmv", "m4a", "m4v", "flv",
+    "webm",
+    // office suites
+    "xls", "xlsx", "ppt", "pptx", "pps", "doc", "docx", "odt", "ods", "odg", "odp",
+    // other
+    "css", "pdf", "exe", "bin", "rss", "dmg", "iso", "apk", "jar", "msi", "deb", "rpm",
+];
+
+/// A configurable extractor of links from HTML responses.
+///
+/// With the default configuration it scans [`DEFAULT_SOURCES`], inline CSS and
+/// plain-text URLs, and only keeps links to the same site as the response.
+#[derive(Debug, Clone)]
+pub struct LinkExtractor {
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+    allow_domains: Vec<String>,
+    deny_domains: Vec<String>,
+    sources: Vec<(String, String)>,
+    allow_cross_site: bool,
+    link_types: Option<Vec<LinkType>>,
+    deny_extensions: Vec<String>,
+    canonicalize: Option<CanonicalizeOptions>,
+    css_links: bool,
+    text_links: bool,
+    nofollow_agent: Option<String>,
+}
+
+impl Default for LinkExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinkExtractor {
+    /// Creates an extractor with the default configuration.
+    pub fn new() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            allow_domains: Vec::new(),
+            deny_domains: Vec::new(),
+            sources: DEFAULT_SOURCES
+                .iter()
+                .map(|(tag, attr)| (tag.to_string(), attr.to_string()))
+                .collect(),
+            allow_cross_site: false,
+            link_types: None,
+            deny_extensions: Vec::new(),
+            canonicalize: None,
+            css_links: true,
+            text_links: true,
+            nofollow_agent: None,
+        }
+    }
+
+    /// Only keeps links whose URL matches `pattern`.
+    ///
+    /// When called several times, a link matching any of the patterns is kept.
+    pub fn allow(mut self, pattern: &str) -> Result<Self, SpiderError> {
+        self.allow.push(compile_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Drops links whose URL matches `pattern`. Takes precedence over [`LinkExtractor::allow`].
+    pub fn deny(mut self, pattern: &str) -> Result<Self, SpiderError> {
+        self.deny.push(compile_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Only keeps links to `domain` or its subdomains.
+    ///
+    /// Allowed domains are kept even when cross-site links are disabled.
+    pub fn allow_domain(mut self, domain: &str) -> Self {
+        self.allow_domains.push(domain.to_ascii_lowercase());
+        self
+    }
+
+    /// Drops links to `domain` or its subdomains.
+    pub fn deny_domain(mut self, domain: &str) -> Self {
+        self.deny_domains.push(domain.to_ascii_lowercase());
+        self
+    }
+
+    /// Replaces the tag/attribute pairs scanned for URLs.
+    pub fn sources<'a, I>(mut self, sources: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        self.sources = sources
+            .into_iter()
+            .map(|(tag, attr)| (tag.to_ascii_lowercase(), attr.to_ascii_lowercase()))
+            .collect();
+        self
+    }
+
+    /// Adds a tag/attribute pair to scan for URLs.
+    pub fn add_source(mut self, tag: &str, attr: &str) -> Self {
+        self.sources
+            .push((tag.to_ascii_lowercase(), attr.to_ascii_lowercase()));
+        self
+    }
+
+    /// Keeps links to other sites. Disabled by default.
+    pub fn allow_cross_site(mut self, allow: bool) -> Self {
+        self.allow_cross_site = allow;
+        self
+    }
+
+    /// Only keeps links of the given types.
+    pub fn link_types<I>(mut self, link_types: I) -> Self
+    where
+        I: IntoIterator<Item = LinkType>,
+    {
+        self.link_types = Some(link_types.into_iter().collect());
+        self
+    }
+
+    /// Drops links whose path ends with one of the given extensions
+    /// (case-insensitive, without the leading dot).
+    pub fn deny_extensions<'a, I>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        self.deny_extensions
+            .extend(extensions.into_iter().map(|ext| ext.to_ascii_lowercase()));
+        self
+    }
+
+    /// Canonicalizes extracted URLs, so equivalent URLs are deduplicated.
+    pub fn canonicalize(mut self, options: CanonicalizeOptions) -> Self {
+        self.canonicalize = Some(options);
+        self
+    }
+
+    /// Also extracts `url(...)` and `@import` references from `<style>`
+    /// elements and `style` attributes. Enabled by default.
+    pub fn css_links(mut self, enabled: bool) -> Self {
+        self.css_links = enabled;
+        self
+    }
+
+    /// Also extracts URLs that appear as plain text. Enabled by default.
+    pub fn text_links(mut self, enabled: bool) -> Self {
+        self.text_links = enabled;
+        self
+    }
+
+    /// Honours `nofollow` for the given crawler: no links are extracted from
+    /// pages whose robots directives for `user_agent` include `nofollow`, and
+    /// links marked `rel="nofollow"` are skipped.
+    pub fn respect_nofollow(mut self, user_agent: &str) -> Self {
+        self.nofollow_agent = Some(user_agent.to_owned());
+        self
+    }
+
+    /// Extracts the links of `response`, in document order and without duplicates.
+    ///
+    /// Relative URLs are resolved against the document's `<base href>`, if any.
+    pub fn extract(&self, response: &Response) -> Vec<Link> {
+        response.with_html(|html| self.extract_from(html, response))
+    }
+
+    fn extract_from(&self, html: &Html, response: &Response) -> Vec<Link> {
+        if let Some(agent) = &self.nofollow_agent
+            && robots::page_directives(html, &response.headers, agent).nofollow
+        {
+            return Vec::new();
+        }
+
+        let base_url = document_base_url(html, &response.url);
+        let mut links = Vec::new();
+        let mut seen = HashSet::new();
+        let finder = LinkFinder::new();
+
+        for node in html.tree.root().descendants() {
+            match node.value() {
+                Node::Element(element) => {
+                    let Some(element_ref) = ElementRef::wrap(node) else {
+                        continue;
+                    };
+                    for (tag, attr) in &self.sources {
+                        if element.name() != tag {
+                            continue;
+                        }
+                        let Some(value) = element.attr(attr) else {
+                            continue;
+                        };
+                        for raw in urls_in_attribute(element, attr, value) {
+                            if let Ok(url) = base_url.join(raw) {
+                                let link_type = link_type_for(element, attr, &url);
+                                let link = element_link(url, link_type, element_ref, attr);
+                                self.push(&mut links, &mut seen, response, link);
+                            }
+                        }
+                    }
+                    if self.css_links
+                        && let Some(style) = element.attr("style")
+                    {
+                        for raw in css_urls(style) {
+                            if let Ok(url) = base_url.join(raw) {
+                                let link_type = css_link_type(&url);
+                                let link = element_link(url, link_type, element_ref, "style");
+                                self.push(&mut links, &mut seen, response, link);
+                            }
+                        }
+                    }
+                }
+                Node::Text(text) => {
+                    let parent = node.parent().and_then(ElementRef::wrap);
+                    if self.css_links
+                        && let Some(style) = parent.filter(|p| p.value().name() == "style")
+                    {
+                        for raw in css_urls(text) {
+                            if let Ok(url) = base_url.join(raw) {
+                                let link_type = css_link_type(&url);
+                                let link = element_link(url, link_type, style, "style");
+                                self.push(&mut links, &mut seen, response, link);
+                            }
+                        }
+                    }
+                    if self.text_links {
+                        for found in finder.links(text) {
+                            if found.kind() == &LinkKind::Url
+                                && let Ok(url) = base_url.join(found.as_str())
+                            {
+                                let mut link = Link::new(url, LinkType::Page);
+                                link.text = Some(found.as_str().to_owned());
+                                link.source = LinkSource::Text;
+                                self.push(&mut links, &mut seen, response, link);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        links
+    }
+
+    fn push(
+        &self,
+        links: &mut Vec<Link>,
+        seen: &mut HashSet<Url>,
+        response: &Response,
+        mut link: Link,
+    ) {
+        if let Some(options) = &self.canonicalize {
+            link.url = canonicalize_url(&link.url, options);
+        }
+
+        if self.nofollow_agent.is_some() && link.is_nofollow() {
+            return;
+        }
+
+        if self.is_allowed(&link.url, &link.link_type, &response.url)
+            && seen.insert(link.url.clone())
+        {
+            links.push(link);
+        }
+    }
+
+    fn is_allowed(&self, url: &Url, link_type: &LinkType, page_url: &Url) -> bool {
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+
+        if let Some(link_types) = &self.link_types
+            && !link_types.contains(link_type)
+        {
+            return false;
+        }
+
+        let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+        if self.deny_domains.iter().any(|d| domain_matches(&host, d)) {
+            return false;
+        }
+        if self.allow_domains.is_empty() {
+            if !self.allow_cross_site && !utils::is_same_site(url, page_url) {
+                return false;
+            }
+        } else if !self.allow_domains.iter().any(|d| domain_matches(&host, d)) {
+            return false;
+        }
+
+        if !self.deny_extensions.is_empty() {
+            let path = url.path().to_ascii_lowercase();
+            if self
+                .deny_extensions
+                .iter()
+                .any(|ext| path.ends_with(&format!(".{}", ext)))
+            {
+                return false;
+            }
+        }
+
+        if self.deny.iter().any(|re| re.is_match(url.as_str())) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|re| re.is_match(url.as_str()))
+    }
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex, SpiderError> {
+    Regex::new(pattern).map_err(|e| {
+        SpiderError::ConfigurationError(format!("Invalid link pattern '{}': {}", pattern, e))
+    })
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Builds a link found in an attribute of `element`.
+fn element_link(url: Url, link_type: LinkType, element: ElementRef, attribute: &str) -> Link {
+    let value = element.value();
+    Link {
+        url,
+        link_type,
+        text: match value.name() {
+            "a" | "area" => element_text(element),
+            _ => None,
+        },
+        title: value.attr("title").map(str::to_owned),
+        rel: value
+            .attr("rel")
+            .map(|rel| {
+                rel.split_ascii_whitespace()
+                    .map(|t| t.to_ascii_lowercase())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        hreflang: value.attr("hreflang").map(str::to_owned),
+        source: LinkSource::Element {
+            tag: value.name().to_owned(),
+            attribute: attribute.to_owned(),
+        },
+    }
+}
+
+/// Returns the raw URLs held by an attribute value.
+fn urls_in_attribute<'a>(element: &Element, attr: &str, value: &'a str) -> Vec<&'a str> {
+    match attr {
+        "srcset" => srcset_urls(value),
+        "content" if element.name() == "meta" => {
+            let is_refresh = element
+                .attr("http-equiv")
+                .is_some_and(|v| v.trim().eq_ignore_ascii_case("refresh"));
+            if is_refresh {
+                meta_refresh_url(value).into_iter().collect()
+            } else {
+                Vec::new()
+            }
+        }
+        _ => {
+            let value = value.trim();
+            if value.is_empty() {
+                Vec::new()
+            } else {
+                vec![value]
+            }
+        }
+    }
+}
+
+/// Splits a `srcset` value into its candidate URLs, dropping the descriptors.
+fn srcset_urls(srcset: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        // A URL directly followed by a comma has no descriptor.
+        let url = rest[..end].trim_end_matches(',');
+        urls.push(url);
+        rest = &rest[end..];
+        // Skip the descriptors up to the next candidate.
+        if url.len() == end {
+            rest = rest.find(',').map_or("", |i| &rest[i..]);
+        }
+    }
+    urls
+}
+
+/// Extracts the target of a `<meta http-equiv="refresh">` content value,
+/// such as `5; url=/next`.
+fn meta_refresh_url(content: &str) -> Option<&str> {
+    let (_, target) = content.split_once([';', ','])?;
+    let target = target.trim_start();
+    let target = if target.len() >= 3 && target[..3].eq_ignore_ascii_case("url") {
+        target[3..].trim_start().strip_prefix('=')?.trim_start()
+    } else {
+        target
+    };
+    let target = target
+        .trim_end()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .trim();
+    (!target.is_empty()).then_some(target)
+}
+
+/// Finds the URLs referenced by `url(...)` and `@import "..."` in CSS text.
+fn css_urls(css: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let lower = css.to_ascii_lowercase();
+
+    let mut offset = 0;
+    while let Some(pos) = lower[offset..].find("url(") {
+        let start = offset + pos + 4;
+        let rest = css[start..].trim_start();
+        let skipped = css.len() - start - rest.len();
+        let (url, consumed) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => match rest[1..].find(quote) {
+                Some(end) => (&rest[1..end + 1], end + 2),
+                None => break,
+            },
+            _ => match rest.find(')') {
+                Some(end) => (rest[..end].trim_end(), end + 1),
+                None => break,
+            },
+        };
+        if !url.is_empty() {
+            urls.push(url);
+        }
+        offset = start + skipped + consumed;
+    }
+
+    let mut offset = 0;
+    while let Some(pos) = lower[offset..].find("@import") {
+        let start = offset + pos + 7;
+        let rest = css[start..].trim_start();
+        if let Some(quote @ ('"' | '\'')) = rest.chars().next()
+            && let Some(end) = rest[1..].find(quote)
+        {
+            urls.push(&rest[1..end + 1]);
+        }
+        offset = start;
+    }
+
+    urls
+}
+
+fn has_extension(url: &Url, extensions: &[&str]) -> bool {
+    let path = url.path();
+    path.rsplit_once('.')
+        .is_some_and(|(_, ext)| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+const FONT_EXTENSIONS: &[&str] = &["woff", "woff2", "ttf", "otf", "eot"];
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "avif", "svg", "bmp", "ico",
+];
+
+/// Classifies a URL referenced from CSS by its extension.
+fn css_link_type(url: &Url) -> LinkType {
+    if has_extension(url, FONT_EXTENSIONS) {
+        LinkType::Font
+    } else if has_extension(url, IMAGE_EXTENSIONS) {
+        LinkType::Image
+    } else if has_extension(url, &["css"]) {
+        LinkType::Stylesheet
+    } else {
+        LinkType::Other("css".to_string())
+    }
+}
+
+/// Returns the whitespace-normalized text of an anchor, or its `alt` text or
+/// that of an image inside it.
+fn element_text(element: ElementRef) -> Option<String> {
+    let text = element
+        .text()
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.is_empty() {
+        // Image links: use the `alt` text of the element or of an image inside it.
+        element
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .find_map(|e| e.value().attr("alt"))
+            .map(|alt| alt.trim().to_owned())
+    } else {
+        Some(text)
+    }
+}
+
+/// Classifies a link by the element and attribute it was found on.
+fn link_type_for(element: &Element, attr: &str, url: &Url) -> LinkType {
+    match (element.name(), attr) {
+        ("a" | "area" | "meta", _) => LinkType::Page,
+        ("link", _) => {
+            let rel = element.attr("rel").unwrap_or_default();
+            let has_rel = |token: &str| {
+                rel.split_ascii_whitespace()
+                    .any(|t| t.eq_ignore_ascii_case(token))
+            };
+            if has_rel("stylesheet") {
+                LinkType::Stylesheet
+            } else if element
+                .attr("as")
+                .is_some_and(|v| v.eq_ignore_ascii_case("font"))
+                || has_extension(url, FONT_EXTENSIONS)
+            {
+                LinkType::Font
+            } else if rel.is_empty() {
+                LinkType::Other("link".to_string())
+            } else {
+                LinkType::Other(rel.to_string())
+            }
+        }
+        ("script", _) => LinkType::Script,
+        ("img", _) | (_, "srcset") | ("video", "poster") => LinkType::Image,
+        ("audio" | "video" | "source" | "object" | "embed", _) => LinkType::Media,
+        ("iframe" | "frame", _) => LinkType::Frame,
+        ("form", _) => LinkType::Form,
+        (name, _) => LinkType::Other(name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use reqwest::StatusCode;
+    use reqwest::header::HeaderMap;
+
+    use super::*;
+
+    const PAGE: &str = r#"<html><body>
+        <a href="/articles/1">One</a>
+        <a href="/articles/1#comments">Comments</a>
+        <a href="/articles/2?b=2&a=1">Two</a>
+        <a href="/articles/2?a=1&b=2">Two again</a>
+        <a href="/articles/3?print=1">Print</a>
+        <a href="/about">About</a>
+        <a href="https://blog.example.com/post">Blog</a>
+        <a href="https://other.org/x">Other</a>
+        <a href="https://cdn.other.org/y.js">CDN</a>
+        <a href="https://notother.org/z">Not other</a>
+        <a href="/files/report.PDF">Report</a>
+        <a href="/archive.tar.gz">Archive</a>
+        <a href="mailto:someone@example.com">Mail</a>
+        <a href="/about">About again</a>
+        <img src="/img/logo.png">
+    </body></html>"#;
+
+    fn response(body: &str) -> Response {
+        Response::new(
+            Url::parse("https://www.example.com/dir/page").unwrap(),
+            StatusCode::OK,
+            HeaderMap::new(),
+            Bytes::from(body.to_owned()),
+        )
+    }
+
+    fn urls(extractor: &LinkExtractor) -> Vec<String> {
+        extractor
+            .extract(&response(PAGE))
+            .into_iter()
+            .map(|link| link.url.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn defaults_keep_same_site_web_links_in_document_order() {
+        assert_eq!(
+            urls(&LinkExtractor::new()),
+            [
+                "https://www.example.com/articles/1",
+                "https://www.example.com/articles/1#comments",
+                "https://www.example.com/articles/2?b=2&a=1",
+                "https://www.example.com/articles/2?a=1&b=2",
+                "https://www.example.com/articles/3?print=1",
+                "https://www.example.com/about",
+                "https://blog.example.com/post",
+                "https://www.example.com/files/report.PDF",
+                "https://www.example.com/archive.tar.gz",
+                "https://www.example.com/img/logo.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn allow_and_deny_patterns() {
+        let articles = LinkExtractor::new().allow(r"/articles/\d+$").unwrap();
+        assert_eq!(urls(&articles), ["https://www.example.com/articles/1"]);
+
+        // Any allowed pattern keeps a link, and a denied one always drops it.
+        let extractor = LinkExtractor::new()
+            .allow(r"/articles/")
+            .unwrap()
+            .allow(r"/about$")
+            .unwrap()
+            .deny(r"print=1|#")
+            .unwrap()
+            .deny(r"b=2&a=1")
+            .unwrap();
+        assert_eq!(
+            urls(&extractor),
+            [
+                "https://www.example.com/articles/1",
+                "https://www.example.com/articles/2?a=1&b=2",
+                "https://www.example.com/about",
+            ]
+        );
+
+        assert!(matches!(
+            LinkExtractor::new().allow("("),
+            Err(SpiderError::ConfigurationError(_))
+        ));
+        assert!(LinkExtractor::new().deny("[").is_err());
+    }
+
+    #[test]
+    fn allow_and_deny_domains() {
+        // Allowed domains include their subdomains, and are kept although
+        // they are on another site.
+        let extractor = LinkExtractor::new().allow_domain("Other.org");
+        assert_eq!(
+            urls(&extractor),
+            ["https://other.org/x", "https://cdn.other.org/y.js"]
+        );
+
+        let extractor = LinkExtractor::new()
+            .allow_domain("other.org")
+            .deny_domain("cdn.other.org");
+        assert_eq!(urls(&extractor), ["https://other.org/x"]);
+
+        let extractor = LinkExtractor::new()
+            .deny_domain("blog.example.com")
+            .allow(r"blog|about")
+            .unwrap();
+        assert_eq!(urls(&extractor), ["https://www.example.com/about"]);
+    }
+
+    #[test]
+    fn cross_site_links() {
+        let extractor = LinkExtractor::new()
+            .allow_cross_site(true)
+            .deny_domain("other.org")
+            .allow(r"org/")
+            .unwrap();
+        assert_eq!(urls(&extractor), ["https://notother.org/z"]);
+
+        let all = urls(&LinkExtractor::new().allow_cross_site(true));
+        assert_eq!(all.len(), 13);
+        assert!(!all.iter().any(|url| url.starts_with("mailto:")));
+    }
+
+    #[test]
+    fn denied_extensions() {
+        let extractor = LinkExtractor::new().deny_extensions(IGNORED_EXTENSIONS.iter().copied());
+        let kept = urls(&extractor);
+        assert_eq!(kept.len(), 7);
+        for denied in ["report.PDF", "archive.tar.gz", "logo.png"] {
+            assert!(!kept.iter().any(|url| url.ends_with(denied)), "{denied}");
+        }
+
+        let extractor = LinkExtractor::new().deny_extensions(["GZ"]);
+        assert!(!urls(&extractor).iter().any(|url| url.ends_with(".gz")));
+    }
+
+    #[test]
+    fn canonicalized_links_are_deduplicated() {
+        let extractor = LinkExtractor::new()
+            .canonicalize(CanonicalizeOptions::default())
+            .allow(r"/articles/")
+            .unwrap();
+        assert_eq!(
+            urls(&extractor),
+            [
+                "https://www.example.com/articles/1",
+                "https://www.example.com/articles/2?a=1&b=2",
+                "https://www.example.com/articles/3?print=1",
+            ]
+        );
+    }
+
+    #[test]
+    fn link_types_combine_with_other_filters() {
+        let extractor = LinkExtractor::new().link_types([LinkType::Image]);
+        assert_eq!(urls(&extractor), ["https://www.example.com/img/logo.png"]);
+
+        let extractor = LinkExtractor::new()
+            .link_types([LinkType::Page])
+            .deny_extensions(["pdf", "gz"])
+            .deny(r"/articles/")
+            .unwrap();
+        assert_eq!(
+            urls(&extractor),
+            [
+                "https://www.example.com/about",
+                "https://blog.example.com/post",
+            ]
+        );
+    }
+
+    #[test]
+    fn only_anchors_have_text() {
+        let page = response(
+            r#"<html><head>
+                <style>body { background: url(/bg.png); }</style>
+                <script src="/app.js">var fallback = 1;</script>
+            </head><body>
+                <a href="/page">  Read
+                   more </a>
+                <a href="/logo"><img src="/logo.png" alt=" Logo "></a>
+                <map><area href="/region" alt="Region"></map>
+                <form action="/search"><label>Query <input name="q"></label></form>
+                <iframe src="/frame">Your browser does not support frames</iframe>
+                <p style="background: url(/inline.png)">Styled paragraph</p>
+            </body></html>"#,
+        );
+        let texts: Vec<(String, Option<String>)> = LinkExtractor::new()
+            .extract(&page)
+            .into_iter()
+            .map(|link| (link.url.path().to_owned(), link.text))
+            .collect();
+        let text = |path: &str| {
+            texts
+                .iter()
+                .find(|(p, _)| p == path)
+                .unwrap_or_else(|| panic!("no link to {path} in {texts:?}"))
+                .1
+                .as_deref()
+        };
+        assert_eq!(text("/page"), Some("Read more"));
+        assert_eq!(text("/logo"), Some("Logo"));
+        assert_eq!(text("/region"), Some("Region"));
+        for path in [
+            "/bg.png",
+            "/app.js",
+            "/logo.png",
+            "/search",
+            "/frame",
+            "/inline.png",
+        ] {
+            assert_eq!(text(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn srcset_candidates() {
+        let cases: &[(&str, &[&str])] = &[
+            ("a.png 1x, b.png 2x", &["a.png", "b.png"]),
+            (
+                "  small.jpg  480w ,  big.jpg 800w  ",
+                &["small.jpg", "big.jpg"],
+            ),
+            ("a.png, b.png", &["a.png", "b.png"]),
+            ("a.png 1x,, ,b.png", &["a.png", "b.png"]),
+            // Commas inside a URL do not split it.
+            ("a.png,b.png", &["a.png,b.png"]),
+            (
+                "data:image/png;base64,AAA= 1x, b.png 2x",
+                &["data:image/png;base64,AAA=", "b.png"],
+            ),
+            ("", &[]),
+            (" , ", &[]),
+        ];
+        for (srcset, expected) in cases {
+            assert_eq!(srcset_urls(srcset), *expected, "{srcset:?}");
+        }
+    }
+
+    #[test]
+    fn meta_refresh_targets() {
+        let cases = [
+            ("5; url=/next", Some("/next")),
+            (
+                "0;URL='https://example.com/x'",
+                Some("https://example.com/x"),
+            ),
+            ("3, url = \"/quoted\" ", Some("/quoted")),
+            ("5; /direct", Some("/direct")),
+            ("5", None),
+            ("5; url=", None),
+            ("5;   ", None),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(meta_refresh_url(content), expected, "{content:?}");
+        }
+
+        let page = response(
+            r#"<meta http-equiv="Refresh" content="0; url=/moved">
+               <meta name="description" content="5; url=/not-a-refresh">"#,
+        );
+        let links = LinkExtractor::new().extract(&page);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url.path(), "/moved");
+        assert_eq!(links[0].link_type, LinkType::Page);
+    }
+
+    #[test]
+    fn css_references() {
+        let cases: &[(&str, &[&str])] = &[
+            ("background: url(a.png)", &["a.png"]),
+            (
+                "a { background: url( 'b.png' ) } b { src: url(\"c.woff2\") URL(d.png) }",
+                &["b.png", "c.woff2", "d.png"],
+            ),
+            // `url()` references come before `@import` strings.
+            (
+                "@import 'e.css'; @import url(f.css); @IMPORT \"g.css\";",
+                &["f.css", "e.css", "g.css"],
+            ),
+            ("url() url('')", &[]),
+            ("url('unterminated", &[]),
+            ("url(a.png", &[]),
+            ("@import;", &[]),
+        ];
+        for (css, expected) in cases {
+            assert_eq!(css_urls(css), *expected, "{css:?}");
+        }
+
+        let page = response(
+            r#"<style>@import "/theme.css"; h1 { background: url(/font.woff2) }</style>
+               <div style="background-image: url('/bg.webp')"></div>"#,
+        );
+        let links: Vec<(String, LinkType)> = LinkExtractor::new()
+            .extract(&page)
+            .into_iter()
+            .map(|link| (link.url.path().to_owned(), link.link_type))
+            .collect();
+        assert_eq!(
+            links,
+            [
+                ("/font.woff2".to_owned(), LinkType::Font),
+                ("/theme.css".to_owned(), LinkType::Stylesheet),
+                ("/bg.webp".to_owned(), LinkType::Image),
+            ]
+        );
+        assert!(
+            LinkExtractor::new()
+                .css_links(false)
+                .extract(&page)
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Typed access to request and response metadata in `spider-lib`.
+//!
+//! `Request` and `Response` carry a `meta` map of `serde_json::Value`s keyed
+//! by name. This module provides the helpers behind their typed accessors
+//! (`get_meta`, `set_meta`, `remove_meta`) and the `MetaKey<T>` type, which
+//! lets crates share a key name together with the type stored under it.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use spider_util::meta::MetaKey;
+//!
+//! const CATEGORY: MetaKey<String> = MetaKey::new("category");
+//!
+//! request.set_typed_meta(&CATEGORY, "books".to_string())?;
+//! let category: Option<String> = request.get_typed_meta(&CATEGORY);
+//! ```
+
+use dashmap::DashMap;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::error::SpiderError;
+
+/// A metadata key bound to the type of the value stored under it.
+pub struct MetaKey<T> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> MetaKey<T> {
+    /// Creates a key with the given name.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the name the value is stored under.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for MetaKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for MetaKey<T> {}
+
+impl<T> fmt::Debug for MetaKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MetaKey").field(&self.name).finish()
+    }
+}
+
+/// The number of times a request has been retried.
+pub const RETRY_ATTEMPTS: MetaKey<u32> = MetaKey::new("retry_attempts");
+
+/// The scheduling priority of a request; higher values are crawled first.
+pub const PRIORITY: MetaKey<i32> = MetaKey::new("priority");
+
+/// The number of links followed from a start request to reach a request.
+pub const DEPTH: MetaKey<u32> = MetaKey::new("depth");
+
+/// The fingerprint of the request whose response discovered a request.
+pub const PARENT_FINGERPRINT: MetaKey<String> = MetaKey::new("parent_fingerprint");
+
+/// Reads and deserializes the value stored under `key`.
+///
+/// Returns `None` if the key is missing or its value does not match `T`.
+pub(crate) fn get<T: DeserializeOwned>(
+    meta: &DashMap<Cow<'static, str>, Value>,
+    key: &str,
+) -> Option<T> {
+    let value = meta.get(key)?.value().clone();
+    serde_json::from_value(value).ok()
+}
+
+/// Serializes `value` and stores it under `key`.
+pub(crate) fn set<T: Serialize>(
+    meta: &DashMap<Cow<'static, str>, Value>,
+    key: Cow<'static, str>,
+    value: T,
+) -> Result<(), SpiderError> {
+    meta.insert(key, serde_json::to_value(value)?);
+    Ok(())
+}
+
+/// Removes the value stored under `key`, returning it.
+pub(crate) fn remove(meta: &DashMap<Cow<'static, str>, Value>, key: &str) -> Option<Value> {
+    meta.remove(key).map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::Deserialize;
+    use serde_json::json;
+    use url::Url;
+
+    use super::*;
+    use crate::request::Request;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Listing {
+        category: String,
+        page: u32,
+    }
+
+    const LISTING: MetaKey<Listing> = MetaKey::new("listing");
+
+    fn request() -> Request {
+        Request::new(Url::parse("https://example.com/").unwrap())
+    }
+
+    #[test]
+    fn typed_values_round_trip() {
+        let mut request = request();
+        let listing = Listing {
+            category: "books".to_owned(),
+            page: 2,
+        };
+        request.set_typed_meta(&LISTING, listing).unwrap();
+        assert_eq!(
+            request.get_meta::<Value>("listing"),
+            Some(json!({ "category": "books", "page": 2 }))
+        );
+        assert_eq!(
+            request.get_typed_meta(&LISTING),
+            Some(Listing {
+                category: "books".to_owned(),
+                page: 2
+            })
+        );
+
+        request
+            .set_meta(Cow::Borrowed("tags"), vec!["a", "b"])
+            .unwrap();
+        assert_eq!(
+            request.get_meta::<Vec<String>>("tags"),
+            Some(vec!["a".to_owned(), "b".to_owned()])
+        );
+
+        assert_eq!(request.remove_typed_meta(&LISTING).map(|l| l.page), Some(2));
+        assert_eq!(request.get_typed_meta(&LISTING), None);
+        assert_eq!(request.remove_meta("tags"), Some(json!(["a", "b"])));
+        assert_eq!(request.remove_meta("tags"), None);
+        assert!(request.meta.is_empty());
+    }
+
+    #[test]
+    fn mismatched_values_read_as_none() {
+        let meta = DashMap::new();
+        set(&meta, Cow::Borrowed(DEPTH.name()), "deep").unwrap();
+        set(
+            &meta,
+            Cow::Borrowed("listing"),
+            json!({ "category": "books" }),
+        )
+        .unwrap();
+        set(&meta, Cow::Borrowed("negative"), -1).unwrap();
+
+        assert_eq!(get::<u32>(&meta, DEPTH.name()), None);
+        assert_eq!(get::<String>(&meta, DEPTH.name()).as_deref(), Some("deep"));
+        assert_eq!(get::<Listing>(&meta, "listing"), None);
+        assert_eq!(get::<u32>(&meta, "negative"), None);
+        assert_eq!(get::<i32>(&meta, "negative"), Some(-1));
+        assert_eq!(get::<u32>(&meta, "missing"), None);
+        // A mismatched value is left in place.
+        assert_eq!(meta.len(), 3);
+
+        let mut request = request().with_meta("listing", json!("not a listing"));
+        assert_eq!(request.get_typed_meta(&LISTING), None);
+        assert_eq!(request.remove_typed_meta(&LISTING), None);
+        assert!(request.meta.is_empty());
+    }
+
+    #[test]
+    fn unserializable_values_are_an_error() {
+        let meta = DashMap::new();
+        let by_pair: HashMap<(u8, u8), u8> = HashMap::from([((1, 2), 3)]);
+        assert!(set(&meta, Cow::Borrowed("pairs"), by_pair).is_err());
+        assert!(meta.is_empty());
+
+        let by_name: BTreeMap<&str, u8> = BTreeMap::from([("a", 1)]);
+        set(&meta, Cow::Borrowed("names"), by_name).unwrap();
+        assert_eq!(remove(&meta, "names"), Some(json!({ "a": 1 })));
+    }
+
+    #[test]
+    fn keys_expose_their_name() {
+        assert_eq!(LISTING.name(), "listing");
+        assert_eq!(format!("{:?}", DEPTH), "MetaKey(\"depth\")");
+        let copy = PRIORITY;
+        assert_eq!(copy.name(), PRIORITY.name());
+    }
+}
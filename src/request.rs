@@ -5,8 +5,10 @@
 //! `spider-lib` framework. It encapsulates all necessary details of an
 //! HTTP request, including:
 //! - The target URL and HTTP method.
-//! - Request headers and an optional request body (supporting JSON, form data, or raw bytes).
-//! - Metadata for tracking retry attempts or other custom information.
+//! - Request headers and an optional request body (supporting JSON, form data,
+//!   multipart form data, or raw bytes).
+//! - Metadata for tracking retry attempts or other custom information, which
+//!   is preserved when a request is serialized.
 //!
 //! Additionally, the module provides methods for building requests,
 //! incrementing retry counters, and generating unique fingerprints
@@ -16,21 +18,382 @@ use bytes::Bytes;
 use dashmap::DashMap;
 use http::header::HeaderMap;
 use reqwest::{Method, Url};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::hash::Hasher;
 use std::str::FromStr;
-use twox_hash::XxHash64;
+use url::form_urlencoded;
 
 use crate::error::SpiderError;
+use crate::fingerprint::{ConfigurableFingerprinter, DefaultFingerprinter, RequestFingerprinter};
+use crate::meta::{self, DEPTH, MetaKey, PARENT_FINGERPRINT, PRIORITY, RETRY_ATTEMPTS};
+use crate::utils::CanonicalizeOptions;
+
+/// Ordered `application/x-www-form-urlencoded` form fields.
+///
+/// Fields keep their insertion order and a key may appear more than once
+/// (`tag=a&tag=b`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormData {
+    fields: Vec<(String, String)>,
+}
+
+impl FormData {
+    /// Creates an empty `FormData`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a field, keeping any existing fields with the same key.
+    pub fn append(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.fields.push((key.into(), value.into()));
+    }
+
+    /// Appends a field and returns the form, for chaining.
+    pub fn with_field(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.append(key, value);
+        self
+    }
+
+    /// Returns the first value for `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns all values for `key`, in insertion order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterates over all fields in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of fields.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns `true` if the form has no fields.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Encodes the form as an `application/x-www-form-urlencoded` string.
+    pub fn to_urlencoded(&self) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(self.iter())
+            .finish()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for FormData {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self {
+            fields: iter
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+impl From<Vec<(String, String)>> for FormData {
+    fn from(fields: Vec<(String, String)>) -> Self {
+        Self { fields }
+    }
+}
+
+/// Converts an unordered map, sorting fields by key so the result is deterministic.
+impl From<DashMap<String, String>> for FormData {
+    fn from(map: DashMap<String, String>) -> Self {
+        let mut fields: Vec<(String, String)> = map.into_iter().collect();
+        fields.sort();
+        Self { fields }
+    }
+}
+
+/// Converts an unordered map, sorting fields by key so the result is deterministic.
+impl From<HashMap<String, String>> for FormData {
+    fn from(map: HashMap<String, String>) -> Self {
+        let mut fields: Vec<(String, String)> = map.into_iter().collect();
+        fields.sort();
+        Self { fields }
+    }
+}
+
+impl Serialize for FormData {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.fields.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FormData {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::{MapAccess, SeqAccess, Visitor};
+        use std::fmt;
+
+        struct FormDataVisitor;
+
+        impl<'de> Visitor<'de> for FormDataVisitor {
+            type Value = FormData;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a list of form fields")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<FormData, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut fields = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(field) = seq.next_element::<(String, String)>()? {
+                    fields.push(field);
+                }
+                Ok(FormData { fields })
+            }
+
+            // Older payloads stored the form as an unordered map.
+            fn visit_map<A>(self, mut map: A) -> Result<FormData, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut fields = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(field) = map.next_entry::<String, String>()? {
+                    fields.push(field);
+                }
+                fields.sort();
+                Ok(FormData { fields })
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(FormDataVisitor)
+        } else {
+            deserializer.deserialize_seq(FormDataVisitor)
+        }
+    }
+}
+
+/// A single part of a `multipart/form-data` body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Part {
+    /// The form field name.
+    pub name: String,
+    /// The file name, for file parts.
+    pub filename: Option<String>,
+    /// The content type of the part, if any.
+    pub content_type: Option<String>,
+    /// The content of the part.
+    pub data: Bytes,
+}
+
+impl Part {
+    /// Creates a plain text field.
+    pub fn text(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            filename: None,
+            content_type: None,
+            data: Bytes::from(value.into()),
+        }
+    }
+
+    /// Creates a file field with an `application/octet-stream` content type.
+    pub fn file(
+        name: impl Into<String>,
+        filename: impl Into<String>,
+        data: impl Into<Bytes>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            filename: Some(filename.into()),
+            content_type: Some("application/octet-stream".to_owned()),
+            data: data.into(),
+        }
+    }
+
+    /// Sets the content type of the part.
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+}
+
+/// A `multipart/form-data` body made of text and file parts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Multipart {
+    /// Creates an empty multipart body with a freshly generated boundary.
+    pub fn new() -> Self {
+        Self {
+            boundary: generate_boundary(),
+            parts: Vec::new(),
+        }
+    }
+
+    /// Adds a plain text field.
+    pub fn text(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.part(Part::text(name, value))
+    }
+
+    /// Adds a file field with the given content type.
+    pub fn file(
+        self,
+        name: impl Into<String>,
+        filename: impl Into<String>,
+        content_type: impl Into<String>,
+        data: impl Into<Bytes>,
+    ) -> Self {
+        self.part(Part::file(name, filename, data).with_content_type(content_type))
+    }
+
+    /// Adds a part. If its content happens to contain the current boundary, a
+    /// new boundary is generated.
+    pub fn part(mut self, part: Part) -> Self {
+        self.parts.push(part);
+        while self
+            .parts
+            .iter()
+            .any(|p| contains_subslice(&p.data, self.boundary.as_bytes()))
+        {
+            self.boundary = generate_boundary();
+        }
+        self
+    }
+
+    /// Returns the boundary separating the parts.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns the parts in insertion order.
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    /// Returns the `Content-Type` header value, including the boundary.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Encodes the parts as a `multipart/form-data` payload.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut out = Vec::new();
+        for part in &self.parts {
+            out.extend_from_slice(b"--");
+            out.extend_from_slice(self.boundary.as_bytes());
+            out.extend_from_slice(b"\r\nContent-Disposition: form-data; name=\"");
+            out.extend_from_slice(escape_disposition_param(&part.name).as_bytes());
+            out.push(b'"');
+            if let Some(filename) = &part.filename {
+                out.extend_from_slice(b"; filename=\"");
+                out.extend_from_slice(escape_disposition_param(filename).as_bytes());
+                out.push(b'"');
+            }
+            out.extend_from_slice(b"\r\n");
+            if let Some(content_type) = &part.content_type {
+                out.extend_from_slice(b"Content-Type: ");
+                out.extend_from_slice(content_type.as_bytes());
+                out.extend_from_slice(b"\r\n");
+            }
+            out.extend_from_slice(b"\r\n");
+            out.extend_from_slice(&part.data);
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(b"--");
+        out.extend_from_slice(self.boundary.as_bytes());
+        out.extend_from_slice(b"--\r\n");
+        Bytes::from(out)
+    }
+}
+
+/// Generates a random multipart boundary.
+fn generate_boundary() -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    let mut high = RandomState::new().build_hasher();
+    high.write_u64(nanos);
+    let mut low = RandomState::new().build_hasher();
+    low.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+
+    format!("----spider-{:016x}{:016x}", high.finish(), low.finish())
+}
+
+/// Escapes a `Content-Disposition` parameter value the way browsers do.
+fn escape_disposition_param(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn contains_subslice(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty() && haystack.windows(needle.len()).any(|w| w == needle)
+}
 
 #[derive(Debug, Clone)]
 pub enum Body {
     Json(Value),
-    Form(DashMap<String, String>),
+    Form(FormData),
     Bytes(Bytes),
+    Multipart(Multipart),
+}
+
+impl Body {
+    /// Returns the `Content-Type` matching this body, if it implies one.
+    pub fn content_type(&self) -> Option<Cow<'static, str>> {
+        match self {
+            Body::Json(_) => Some(Cow::Borrowed("application/json")),
+            Body::Form(_) => Some(Cow::Borrowed("application/x-www-form-urlencoded")),
+            Body::Bytes(_) => None,
+            Body::Multipart(multipart) => Some(Cow::Owned(multipart.content_type())),
+        }
+    }
+
+    /// Encodes the body into the bytes sent over the wire.
+    pub fn to_bytes(&self) -> Result<Bytes, SpiderError> {
+        match self {
+            Body::Json(value) => Ok(Bytes::from(serde_json::to_vec(value)?)),
+            Body::Form(form) => Ok(Bytes::from(form.to_urlencoded())),
+            Body::Bytes(bytes) => Ok(bytes.clone()),
+            Body::Multipart(multipart) => Ok(multipart.to_bytes()),
+        }
+    }
 }
 
 // Custom serialization for Body enum
@@ -40,15 +403,17 @@ impl Serialize for Body {
         S: serde::Serializer,
     {
         use serde::ser::SerializeMap;
+        // Binary formats cannot deserialize a self-describing `Value`, so JSON
+        // bodies are stored as their string encoding there.
+        let human_readable = serializer.is_human_readable();
         let mut map = serializer.serialize_map(Some(1))?;
 
         match self {
-            Body::Json(value) => map.serialize_entry("Json", value)?,
-            Body::Form(dashmap) => {
-                let hmap: HashMap<String, String> = dashmap.clone().into_iter().collect();
-                map.serialize_entry("Form", &hmap)?
-            }
+            Body::Json(value) if human_readable => map.serialize_entry("Json", value)?,
+            Body::Json(value) => map.serialize_entry("Json", &value.to_string())?,
+            Body::Form(form) => map.serialize_entry("Form", form)?,
             Body::Bytes(bytes) => map.serialize_entry("Bytes", bytes)?,
+            Body::Multipart(multipart) => map.serialize_entry("Multipart", multipart)?,
         }
 
         map.end()
@@ -63,7 +428,9 @@ impl<'de> Deserialize<'de> for Body {
         use serde::de::{self, MapAccess, Visitor};
         use std::fmt;
 
-        struct BodyVisitor;
+        struct BodyVisitor {
+            human_readable: bool,
+        }
 
         impl<'de> Visitor<'de> for BodyVisitor {
             type Value = Body;
@@ -76,34 +443,164 @@ impl<'de> Deserialize<'de> for Body {
             where
                 V: MapAccess<'de>,
             {
-                let entry = map.next_entry::<String, Value>()?;
-                let (key, value) = match entry {
-                    Some((k, v)) => (k, v),
-                    None => return Err(de::Error::custom("Expected a body variant")),
+                let Some(key) = map.next_key::<String>()? else {
+                    return Err(de::Error::custom("Expected a body variant"));
                 };
 
                 match key.as_str() {
-                    "Json" => Ok(Body::Json(value)),
-                    "Form" => {
-                        let form_data: HashMap<String, String> =
-                            serde_json::from_value(value).map_err(de::Error::custom)?;
-                        let dashmap = DashMap::new();
-                        for (k, v) in form_data {
-                            dashmap.insert(k, v);
-                        }
-                        Ok(Body::Form(dashmap))
-                    }
-                    "Bytes" => {
-                        let bytes: Bytes =
-                            serde_json::from_value(value).map_err(de::Error::custom)?;
-                        Ok(Body::Bytes(bytes))
+                    "Json" if self.human_readable => Ok(Body::Json(map.next_value()?)),
+                    "Json" => {
+                        let encoded: String = map.next_value()?;
+                        let value = serde_json::from_str(&encoded).map_err(de::Error::custom)?;
+                        Ok(Body::Json(value))
                     }
+                    "Form" => Ok(Body::Form(map.next_value()?)),
+                    "Bytes" => Ok(Body::Bytes(map.next_value()?)),
+                    "Multipart" => Ok(Body::Multipart(map.next_value()?)),
                     _ => Err(de::Error::custom(format!("Unknown body variant: {}", key))),
                 }
             }
         }
 
-        deserializer.deserialize_map(BodyVisitor)
+        let human_readable = deserializer.is_human_readable();
+        deserializer.deserialize_map(BodyVisitor { human_readable })
+    }
+}
+
+/// Serializes request metadata.
+///
+/// Human-readable formats get a plain map of values; binary formats get a list
+/// of `(key, json)` pairs, since they cannot deserialize a `Value` directly.
+struct SerializeMeta<'a>(&'a DashMap<Cow<'static, str>, Value>);
+
+impl Serialize for SerializeMeta<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut entries: Vec<(String, Value)> = self
+            .0
+            .iter()
+            .map(|entry| (entry.key().to_string(), entry.value().clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if serializer.is_human_readable() {
+            serializer.collect_map(entries)
+        } else {
+            serializer.collect_seq(entries.into_iter().map(|(k, v)| (k, v.to_string())))
+        }
+    }
+}
+
+/// Deserializes request metadata written by [`SerializeMeta`].
+struct DeserializeMeta(DashMap<Cow<'static, str>, Value>);
+
+impl<'de> Deserialize<'de> for DeserializeMeta {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de;
+
+        let meta = DashMap::new();
+        if deserializer.is_human_readable() {
+            let entries: HashMap<String, Value> = HashMap::deserialize(deserializer)?;
+            for (key, value) in entries {
+                meta.insert(Cow::Owned(key), value);
+            }
+        } else {
+            let entries: Vec<(String, String)> = Vec::deserialize(deserializer)?;
+            for (key, encoded) in entries {
+                let value = serde_json::from_str(&encoded).map_err(de::Error::custom)?;
+                meta.insert(Cow::Owned(key), value);
+            }
+        }
+        Ok(DeserializeMeta(meta))
+    }
+}
+
+/// A header value as written to human-readable formats: a string when the
+/// bytes are valid UTF-8, the raw bytes otherwise.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum HeaderValueRepr {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+/// Serializes a `HeaderMap` as a list of `(name, value)` pairs.
+///
+/// Every value of a multi-valued header gets its own pair, and values that
+/// are not valid UTF-8 are kept as raw bytes, so nothing is dropped.
+struct SerializeHeaders<'a>(&'a HeaderMap);
+
+impl Serialize for SerializeHeaders<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // Binary formats need the sequence length up front, which the
+        // `HeaderMap` iterator does not report, so collect first.
+        if serializer.is_human_readable() {
+            let pairs: Vec<(&str, HeaderValueRepr)> = self
+                .0
+                .iter()
+                .map(|(name, value)| {
+                    let value = match std::str::from_utf8(value.as_bytes()) {
+                        Ok(text) => HeaderValueRepr::Text(text.to_owned()),
+                        Err(_) => HeaderValueRepr::Bytes(value.as_bytes().to_vec()),
+                    };
+                    (name.as_str(), value)
+                })
+                .collect();
+            pairs.serialize(serializer)
+        } else {
+            let pairs: Vec<(&str, &[u8])> = self
+                .0
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_bytes()))
+                .collect();
+            pairs.serialize(serializer)
+        }
+    }
+}
+
+/// Deserializes a `HeaderMap` written by [`SerializeHeaders`].
+///
+/// Repeated names are appended rather than overwritten. An invalid header
+/// name or value is an error.
+struct DeserializeHeaders(HeaderMap);
+
+impl<'de> Deserialize<'de> for DeserializeHeaders {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de;
+
+        let pairs: Vec<(String, Vec<u8>)> = if deserializer.is_human_readable() {
+            Vec::<(String, HeaderValueRepr)>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(name, value)| match value {
+                    HeaderValueRepr::Text(text) => (name, text.into_bytes()),
+                    HeaderValueRepr::Bytes(bytes) => (name, bytes),
+                })
+                .collect()
+        } else {
+            Vec::deserialize(deserializer)?
+        };
+
+        let mut header_map = HeaderMap::with_capacity(pairs.len());
+        for (name, value) in pairs {
+            let header_name = http::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| de::Error::custom(format!("Invalid header name '{}': {}", name, e)))?;
+            let header_value = http::header::HeaderValue::from_bytes(&value).map_err(|e| {
+                de::Error::custom(format!("Invalid value for header '{}': {}", name, e))
+            })?;
+            header_map.append(header_name, header_value);
+        }
+        Ok(DeserializeHeaders(header_map))
     }
 }
 
@@ -116,6 +613,14 @@ pub struct Request {
     pub meta: DashMap<Cow<'static, str>, Value>,
 }
 
+/// The first field of requests serialized to binary formats, which tells
+/// them apart from those written before the metadata was serialized.
+///
+/// Binary formats do not mark where a struct ends, so the old four-field
+/// layout (url, method, headers, body) cannot be detected from a missing
+/// fifth field. The old layout starts with the URL, which this is not.
+const BINARY_LAYOUT_V2: &str = "spider-util/request/2";
+
 // Custom serialization for Request struct
 impl Serialize for Request {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -123,23 +628,19 @@ impl Serialize for Request {
         S: serde::Serializer,
     {
         use serde::ser::SerializeStruct;
-        // Convert HeaderMap to a serializable format
-        let headers_vec: Vec<(String, String)> = self
-            .headers
-            .iter()
-            .filter_map(|(name, value)| {
-                value
-                    .to_str()
-                    .ok()
-                    .map(|val_str| (name.as_str().to_string(), val_str.to_string()))
-            })
-            .collect();
 
-        let mut s = serializer.serialize_struct("Request", 5)?;
+        let mut s = if serializer.is_human_readable() {
+            serializer.serialize_struct("Request", 5)?
+        } else {
+            let mut s = serializer.serialize_struct("Request", 6)?;
+            s.serialize_field("layout", BINARY_LAYOUT_V2)?;
+            s
+        };
         s.serialize_field("url", &self.url.as_str())?;
         s.serialize_field("method", &self.method.as_str())?;
-        s.serialize_field("headers", &headers_vec)?;
+        s.serialize_field("headers", &SerializeHeaders(&self.headers))?;
         s.serialize_field("body", &self.body)?;
+        s.serialize_field("meta", &SerializeMeta(&self.meta))?;
         s.end()
     }
 }
@@ -149,7 +650,7 @@ impl<'de> Deserialize<'de> for Request {
     where
         D: serde::Deserializer<'de>,
     {
-        use serde::de::{self, MapAccess, Visitor};
+        use serde::de::{self, MapAccess, SeqAccess, Visitor};
         use std::fmt;
 
         #[derive(Deserialize)]
@@ -159,9 +660,12 @@ impl<'de> Deserialize<'de> for Request {
             Method,
             Headers,
             Body,
+            Meta,
         }
 
-        struct RequestVisitor;
+        struct RequestVisitor {
+            binary: bool,
+        }
 
         impl<'de> Visitor<'de> for RequestVisitor {
             type Value = Request;
@@ -170,6 +674,40 @@ impl<'de> Deserialize<'de> for Request {
                 formatter.write_str("struct Request")
             }
 
+            fn visit_seq<V>(self, mut seq: V) -> Result<Request, V::Error>
+            where
+                V: SeqAccess<'de>,
+            {
+                let mut url_str: String = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                // Binary payloads without the layout marker were written
+                // before the metadata was serialized and end after the body.
+                let has_meta = !self.binary || url_str == BINARY_LAYOUT_V2;
+                if self.binary && has_meta {
+                    url_str = seq
+                        .next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                }
+                let method_str: String = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                let headers: DeserializeHeaders = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                let body: Option<Body> = seq.next_element()?.flatten();
+                let meta: Option<DeserializeMeta> =
+                    if has_meta { seq.next_element()? } else { None };
+
+                Ok(Request {
+                    url: Url::parse(&url_str).map_err(de::Error::custom)?,
+                    method: Method::from_str(&method_str).map_err(de::Error::custom)?,
+                    headers: headers.0,
+                    body,
+                    meta: meta.map(|m| m.0).unwrap_or_default(),
+                })
+            }
+
             fn visit_map<V>(self, mut map: V) -> Result<Request, V::Error>
             where
                 V: MapAccess<'de>,
@@ -177,7 +715,8 @@ impl<'de> Deserialize<'de> for Request {
                 let mut url = None;
                 let mut method = None;
                 let mut headers = None;
-                let mut body = None;
+                let mut body: Option<Option<Body>> = None;
+                let mut meta: Option<DeserializeMeta> = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -202,19 +741,8 @@ impl<'de> Deserialize<'de> for Request {
                             if headers.is_some() {
                                 return Err(de::Error::duplicate_field("headers"));
                             }
-                            // Deserialize headers vector and convert back to HeaderMap
-                            let headers_vec: Vec<(String, String)> = map.next_value()?;
-                            let mut header_map = HeaderMap::new();
-                            for (name, value) in headers_vec {
-                                if let Ok(header_name) =
-                                    http::header::HeaderName::from_bytes(name.as_bytes())
-                                    && let Ok(header_value) =
-                                        http::header::HeaderValue::from_str(&value)
-                                {
-                                    header_map.insert(header_name, header_value);
-                                }
-                            }
-                            headers = Some(header_map);
+                            let header_map: DeserializeHeaders = map.next_value()?;
+                            headers = Some(header_map.0);
                         }
                         Field::Body => {
                             if body.is_some() {
@@ -222,26 +750,40 @@ impl<'de> Deserialize<'de> for Request {
                             }
                             body = Some(map.next_value()?);
                         }
+                        Field::Meta => {
+                            if meta.is_some() {
+                                return Err(de::Error::duplicate_field("meta"));
+                            }
+                            meta = Some(map.next_value()?);
+                        }
                     }
                 }
 
                 let url = url.ok_or_else(|| de::Error::missing_field("url"))?;
                 let method = method.ok_or_else(|| de::Error::missing_field("method"))?;
                 let headers = headers.ok_or_else(|| de::Error::missing_field("headers"))?;
-                let body = body; // Optional field
+                let body = body.flatten(); // Optional field
+                // Optional field, absent from payloads written by older versions
+                let meta = meta.map(|m| m.0).unwrap_or_default();
 
                 Ok(Request {
                     url,
                     method,
                     headers,
                     body,
-                    meta: DashMap::new(), // Initialize empty meta map
+                    meta,
                 })
             }
         }
 
-        const FIELDS: &[&str] = &["url", "method", "headers", "body"];
-        deserializer.deserialize_struct("Request", FIELDS, RequestVisitor)
+        const FIELDS: &[&str] = &["url", "method", "headers", "body", "meta"];
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_struct("Request", FIELDS, RequestVisitor { binary: false })
+        } else {
+            // At most six fields are read: the layout marker and the five
+            // above, or only the four fields of the old layout.
+            deserializer.deserialize_tuple(6, RequestVisitor { binary: true })
+        }
     }
 }
 
@@ -301,8 +843,8 @@ impl Request {
     }
 
     /// Sets the body of the request to a form.
-    pub fn with_form(self, form: DashMap<String, String>) -> Self {
-        self.with_body(Body::Form(form))
+    pub fn with_form(self, form: impl Into<FormData>) -> Self {
+        self.with_body(Body::Form(form.into()))
     }
 
     /// Sets the body of the request to a byte slice.
@@ -310,18 +852,61 @@ impl Request {
         self.with_body(Body::Bytes(bytes))
     }
 
+    /// Sets the body of the request to a `multipart/form-data` payload.
+    pub fn with_multipart(self, multipart: Multipart) -> Self {
+        self.with_body(Body::Multipart(multipart))
+    }
+
     /// Adds a value to the request's metadata.
     pub fn with_meta(self, key: &str, value: Value) -> Self {
         self.meta.insert(Cow::Owned(key.to_owned()), value);
         self
     }
 
-    const RETRY_ATTEMPTS_KEY: &str = "retry_attempts";
+    /// Reads a metadata value, deserialized as `T`.
+    ///
+    /// Returns `None` if the key is missing or its value does not match `T`.
+    pub fn get_meta<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        meta::get(&self.meta, key)
+    }
+
+    /// Serializes `value` and stores it in the request's metadata.
+    pub fn set_meta<T: Serialize>(
+        &mut self,
+        key: impl Into<Cow<'static, str>>,
+        value: T,
+    ) -> Result<(), SpiderError> {
+        meta::set(&self.meta, key.into(), value)
+    }
+
+    /// Removes a metadata value, returning it.
+    pub fn remove_meta(&mut self, key: &str) -> Option<Value> {
+        meta::remove(&self.meta, key)
+    }
+
+    /// Reads the metadata value stored under a typed key.
+    pub fn get_typed_meta<T: DeserializeOwned>(&self, key: &MetaKey<T>) -> Option<T> {
+        meta::get(&self.meta, key.name())
+    }
+
+    /// Stores a metadata value under a typed key.
+    pub fn set_typed_meta<T: Serialize>(
+        &mut self,
+        key: &MetaKey<T>,
+        value: T,
+    ) -> Result<(), SpiderError> {
+        meta::set(&self.meta, Cow::Borrowed(key.name()), value)
+    }
+
+    /// Removes the metadata value stored under a typed key, returning it.
+    pub fn remove_typed_meta<T: DeserializeOwned>(&mut self, key: &MetaKey<T>) -> Option<T> {
+        meta::remove(&self.meta, key.name()).and_then(|v| serde_json::from_value(v).ok())
+    }
 
     /// Gets the number of times the request has been retried.
     pub fn get_retry_attempts(&self) -> u32 {
         self.meta
-            .get(Self::RETRY_ATTEMPTS_KEY)
+            .get(RETRY_ATTEMPTS.name())
             .and_then(|v| v.value().as_u64())
             .unwrap_or(0) as u32
     }
@@ -330,37 +915,194 @@ impl Request {
     pub fn increment_retry_attempts(&mut self) {
         let current_attempts = self.get_retry_attempts();
         self.meta.insert(
-            Cow::Borrowed(Self::RETRY_ATTEMPTS_KEY),
+            Cow::Borrowed(RETRY_ATTEMPTS.name()),
             Value::from(current_attempts + 1),
         );
     }
 
+    /// Sets the scheduling priority of the request; higher values are crawled first.
+    pub fn with_priority(self, priority: i32) -> Self {
+        self.meta
+            .insert(Cow::Borrowed(PRIORITY.name()), Value::from(priority));
+        self
+    }
+
+    /// Gets the scheduling priority of the request, defaulting to 0.
+    pub fn get_priority(&self) -> i32 {
+        self.meta
+            .get(PRIORITY.name())
+            .and_then(|v| v.value().as_i64())
+            .unwrap_or(0) as i32
+    }
+
+    /// Sets the crawl depth of the request.
+    pub fn with_depth(self, depth: u32) -> Self {
+        self.meta
+            .insert(Cow::Borrowed(DEPTH.name()), Value::from(depth));
+        self
+    }
+
+    /// Gets the crawl depth of the request; start requests have depth 0.
+    pub fn get_depth(&self) -> u32 {
+        self.meta
+            .get(DEPTH.name())
+            .and_then(|v| v.value().as_u64())
+            .unwrap_or(0) as u32
+    }
+
+    /// Records the fingerprint of the request whose response discovered this one.
+    pub fn with_parent_fingerprint(self, fingerprint: impl Into<String>) -> Self {
+        self.meta.insert(
+            Cow::Borrowed(PARENT_FINGERPRINT.name()),
+            Value::from(fingerprint.into()),
+        );
+        self
+    }
+
+    /// Gets the fingerprint of the request whose response discovered this one.
+    pub fn get_parent_fingerprint(&self) -> Option<String> {
+        self.meta
+            .get(PARENT_FINGERPRINT.name())
+            .and_then(|v| v.value().as_str().map(str::to_owned))
+    }
+
     /// Generates a unique fingerprint for the request based on its URL, method, and body.
     pub fn fingerprint(&self) -> String {
-        let mut hasher = XxHash64::default();
-        hasher.write(self.url.as_str().as_bytes());
-        hasher.write(self.method.as_str().as_bytes());
-
-        if let Some(ref body) = self.body {
-            match body {
-                Body::Json(json_val) => {
-                    if let Ok(serialized) = serde_json::to_string(json_val) {
-                        hasher.write(serialized.as_bytes());
-                    }
-                }
-                Body::Form(form_val) => {
-                    let mut form_string = String::new();
-                    for r in form_val.iter() {
-                        form_string.push_str(r.key());
-                        form_string.push_str(r.value());
-                    }
-                    hasher.write(form_string.as_bytes());
-                }
-                Body::Bytes(bytes_val) => {
-                    hasher.write(bytes_val);
-                }
-            }
-        }
-        format!("{:x}", hasher.finish())
+        DefaultFingerprinter.fingerprint(self)
+    }
+
+    /// Generates a fingerprint like [`Request::fingerprint`], but hashes the
+    /// canonical form of the URL so that equivalent URLs (reordered query,
+    /// default port, fragment, ...) produce the same fingerprint.
+    pub fn canonical_fingerprint(&self, options: &CanonicalizeOptions) -> String {
+        ConfigurableFingerprinter::new()
+            .with_canonicalization(options.clone())
+            .fingerprint(self)
+    }
+
+    /// Generates a fingerprint of the request using a custom fingerprinter.
+    pub fn fingerprint_with(&self, fingerprinter: &dyn RequestFingerprinter) -> String {
+        fingerprinter.fingerprint(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_request() -> Request {
+        Request::new(Url::parse("https://example.com/api?page=2").unwrap())
+            .with_method(Method::POST)
+            .with_header("x-token", "abc")
+            .unwrap()
+            .with_json(json!({ "query": "books", "filters": { "tags": ["a", "b"], "max": 1.5 } }))
+            .with_meta(
+                "context",
+                json!({ "nested": { "list": [1, null, "x"], "flag": true } }),
+            )
+            .with_priority(3)
+            .with_depth(2)
+    }
+
+    fn assert_same(left: &Request, right: &Request) {
+        assert_eq!(left.url, right.url);
+        assert_eq!(left.method, right.method);
+        assert_eq!(left.headers, right.headers);
+        assert_eq!(
+            serde_json::to_value(&left.body).unwrap(),
+            serde_json::to_value(&right.body).unwrap()
+        );
+        let meta = |request: &Request| {
+            request
+                .meta
+                .iter()
+                .map(|entry| (entry.key().to_string(), entry.value().clone()))
+                .collect::<HashMap<_, _>>()
+        };
+        assert_eq!(meta(left), meta(right));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let request = sample_request();
+        let encoded = serde_json::to_string(&request).unwrap();
+        let decoded: Request = serde_json::from_str(&encoded).unwrap();
+        assert_same(&request, &decoded);
+        assert!(matches!(decoded.body, Some(Body::Json(_))));
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let request = sample_request();
+        let encoded = bincode::serialize(&request).unwrap();
+        let decoded: Request = bincode::deserialize(&encoded).unwrap();
+        assert_same(&request, &decoded);
+        assert_eq!(
+            decoded.get_meta::<Value>("context"),
+            Some(json!({ "nested": { "list": [1, null, "x"], "flag": true } }))
+        );
+    }
+
+    /// The layout of requests serialized before the metadata was.
+    fn legacy_request(url: &str) -> (&str, &str, Vec<(&str, &str)>, Option<Body>) {
+        (url, "GET", vec![("accept", "text/html")], None)
+    }
+
+    #[test]
+    fn bincode_payload_without_meta() {
+        let encoded = bincode::serialize(&legacy_request("https://example.com/")).unwrap();
+        let decoded: Request = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(decoded.url.as_str(), "https://example.com/");
+        assert_eq!(decoded.headers["accept"], "text/html");
+        assert!(decoded.meta.is_empty());
+    }
+
+    #[test]
+    fn bincode_sequence_of_payloads_without_meta() {
+        let legacy = vec![
+            legacy_request("https://example.com/a"),
+            legacy_request("https://example.com/b"),
+            legacy_request("https://example.com/c"),
+        ];
+        let encoded = bincode::serialize(&legacy).unwrap();
+        let decoded: Vec<Request> = bincode::deserialize(&encoded).unwrap();
+        let urls: Vec<&str> = decoded.iter().map(|request| request.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/a",
+                "https://example.com/b",
+                "https://example.com/c"
+            ]
+        );
+        assert!(decoded.iter().all(|request| request.meta.is_empty()));
+    }
+
+    #[test]
+    fn bincode_sequence_round_trip() {
+        let requests = vec![
+            sample_request(),
+            Request::new(Url::parse("https://example.com/").unwrap()),
+        ];
+        let encoded = bincode::serialize(&requests).unwrap();
+        let decoded: Vec<Request> = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_same(&requests[0], &decoded[0]);
+        assert_same(&requests[1], &decoded[1]);
+    }
+
+    #[test]
+    fn bincode_invalid_meta_is_an_error() {
+        let payload = (
+            BINARY_LAYOUT_V2,
+            "https://example.com/",
+            "GET",
+            Vec::<(&str, &str)>::new(),
+            None::<Body>,
+            vec![("context", "{not json")],
+        );
+        let encoded = bincode::serialize(&payload).unwrap();
+        assert!(bincode::deserialize::<Request>(&encoded).is_err());
     }
 }
@@ -11,17 +11,32 @@
 //! - `Link` and `LinkType` enums for structured representation and extraction
 //!   of hyperlinks found within the response content.
 
+use crate::embedded_json;
+use crate::error::SpiderError;
+use crate::feed::{self, Feed, FeedLink};
+use crate::json_path::get_cached_json_path;
+use crate::link_extractor::LinkExtractor;
+use crate::meta::{self, DEPTH, MetaKey};
 use crate::request::Request;
+use crate::robots::{self, RobotsDirectives};
+use crate::selector::{SelectorList, SharedDocument};
 use crate::selector_cache::get_cached_selector;
-use crate::utils;
+use crate::sitemap::SitemapParser;
+use crate::structured_data::StructuredData;
+use crate::table::{self, Table};
+use crate::xpath::{self, XPathValue, get_cached_xpath};
 use bytes::Bytes;
 use dashmap::{DashMap, DashSet};
-use linkify::{LinkFinder, LinkKind};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use parking_lot::{Mutex, ReentrantMutex};
 use reqwest::StatusCode;
-use reqwest::header::HeaderMap;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue, REFERER};
 use scraper::Html;
+use serde::Serialize;
 use serde::de::DeserializeOwned;
-use serde_json::{self, Value};
+use serde_json::{self, Map, Value};
+use std::fmt;
+use std::sync::Arc;
 use std::{borrow::Cow, str::Utf8Error, str::from_utf8};
 use url::Url;
 
@@ -36,12 +51,36 @@ pub enum LinkType {
     Stylesheet,
     /// A link to an image.
     Image,
-    /// A link to a media file (audio/video).
+    /// A link to a media file (audio/video) or embedded object.
     Media,
+    /// A link to a document loaded in an `<iframe>` or `<frame>`.
+    Frame,
+    /// The action URL of a `<form>`.
+    Form,
+    /// A link to a web font.
+    Font,
     /// A link to another type of resource.
     Other(String),
 }
 
+/// Represents where a link was found in the document.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LinkSource {
+    /// An attribute of an element, such as `a[href]` or `img[src]`. URLs found
+    /// in inline CSS use the `style` attribute (or the `<style>` element).
+    Element {
+        /// The element's tag name.
+        tag: String,
+        /// The attribute holding the URL.
+        attribute: String,
+    },
+    /// A URL found in the document's text.
+    Text,
+    /// A link that was not extracted from a document, such as one built
+    /// with [`Link::new`].
+    Unknown,
+}
+
 /// Represents a link discovered on a web page.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Link {
@@ -49,6 +88,48 @@ pub struct Link {
     pub url: Url,
     /// The type of the discovered link.
     pub link_type: LinkType,
+    /// The whitespace-normalized anchor text of `<a>` and `<area>` links,
+    /// falling back to their `alt` text or that of an image inside; for links
+    /// found in text, the matched URL text. `None` for other elements.
+    pub text: Option<String>,
+    /// The element's `title` attribute.
+    pub title: Option<String>,
+    /// The lowercased tokens of the element's `rel` attribute.
+    pub rel: Vec<String>,
+    /// The element's `hreflang` attribute.
+    pub hreflang: Option<String>,
+    /// Where the link was found.
+    pub source: LinkSource,
+}
+
+impl Link {
+    /// Creates a link of unknown source, with no other details.
+    pub fn new(url: Url, link_type: LinkType) -> Self {
+        Self {
+            url,
+            link_type,
+            text: None,
+            title: None,
+            rel: Vec::new(),
+            hreflang: None,
+            source: LinkSource::Unknown,
+        }
+    }
+
+    /// Returns `true` if the `rel` attribute contains `token` (case-insensitive).
+    pub fn has_rel(&self, token: &str) -> bool {
+        self.rel.iter().any(|t| t.eq_ignore_ascii_case(token))
+    }
+
+    /// Returns `true` if the link is marked `rel="nofollow"`.
+    pub fn is_nofollow(&self) -> bool {
+        self.has_rel("nofollow")
+    }
+
+    /// Returns `true` if the link was found in plain text rather than an element.
+    pub fn is_from_text(&self) -> bool {
+        self.source == LinkSource::Text
+    }
 }
 
 /// Represents an HTTP response received from a server.
@@ -68,104 +149,563 @@ pub struct Response {
     pub meta: DashMap<Cow<'static, str>, Value>,
     /// Indicates if the response was served from a cache.
     pub cached: bool,
+    /// The fingerprint of the request that led to this response, as computed
+    /// by the scheduler or downloader. It becomes the parent fingerprint of
+    /// the requests followed from this response.
+    pub request_fingerprint: Option<String>,
+    /// The documents parsed from the body, filled in on first use.
+    documents: DocumentCache,
+}
+
+/// The documents parsed from a response body, so that every helper of a
+/// [`Response`] shares a single parse.
+///
+/// A document is parsed on first use and dropped once the body it was parsed
+/// from is replaced or decodes with another encoding, as after a change of
+/// the `Content-Type` charset. Clones of a response share the parsed
+/// documents.
+#[derive(Default)]
+struct DocumentCache {
+    html: Mutex<Option<CachedDocument>>,
+    xml: Mutex<Option<CachedDocument>>,
+}
+
+#[derive(Clone)]
+struct CachedDocument {
+    /// The body the document was parsed from, which also keeps its address
+    /// from being reused while the entry lives.
+    body: Bytes,
+    /// The encoding the body was decoded with.
+    encoding: &'static Encoding,
+    document: SharedDocument,
+}
+
+impl DocumentCache {
+    fn get_or_parse<F>(
+        slot: &Mutex<Option<CachedDocument>>,
+        body: &Bytes,
+        encoding: &'static Encoding,
+        parse: F,
+    ) -> Result<SharedDocument, SpiderError>
+    where
+        F: FnOnce() -> Result<Html, SpiderError>,
+    {
+        let mut slot = slot.lock();
+        if let Some(cached) = slot.as_ref()
+            && cached.body.as_ptr() == body.as_ptr()
+            && cached.body.len() == body.len()
+            && cached.encoding == encoding
+        {
+            return Ok(Arc::clone(&cached.document));
+        }
+
+        let document = Arc::new(ReentrantMutex::new(parse()?));
+        *slot = Some(CachedDocument {
+            body: body.clone(),
+            encoding,
+            document: Arc::clone(&document),
+        });
+        Ok(document)
+    }
+
+    fn clear(&self) {
+        *self.html.lock() = None;
+        *self.xml.lock() = None;
+    }
+}
+
+impl Clone for DocumentCache {
+    fn clone(&self) -> Self {
+        Self {
+            html: Mutex::new(self.html.lock().clone()),
+            xml: Mutex::new(self.xml.lock().clone()),
+        }
+    }
 }
 
+impl fmt::Debug for DocumentCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DocumentCache")
+            .field("html", &self.html.lock().is_some())
+            .field("xml", &self.xml.lock().is_some())
+            .finish()
+    }
+}
 
 impl Response {
+    /// Creates a response for `url` that was not served from a cache.
+    ///
+    /// The request URL is set to `url` and the metadata is empty; both can
+    /// be changed through their fields. This is the only way to build a
+    /// response, since the cache of parsed documents is private.
+    pub fn new(url: Url, status: StatusCode, headers: HeaderMap, body: Bytes) -> Self {
+        Response {
+            request_url: url.clone(),
+            url,
+            status,
+            headers,
+            body,
+            meta: DashMap::new(),
+            cached: false,
+            request_fingerprint: None,
+            documents: DocumentCache::default(),
+        }
+    }
+
+    /// Records the fingerprint of the request that led to this response.
+    pub fn with_request_fingerprint(mut self, fingerprint: impl Into<String>) -> Self {
+        self.request_fingerprint = Some(fingerprint.into());
+        self
+    }
+
     /// Reconstructs the original `Request` that led to this response.
+    ///
+    /// The metadata is carried over unchanged, including its priority and depth.
     pub fn request_from_response(&self) -> Request {
         let mut request = Request::new(self.request_url.clone());
         request.meta = self.meta.clone();
         request
     }
 
+    /// Creates a request for a URL discovered in this response.
+    ///
+    /// The new request is one level deeper and sends this response's URL as
+    /// its `Referer`. If the response has a [`Response::request_fingerprint`],
+    /// it is recorded as the parent of the new request. No other metadata is
+    /// carried over, see
+    /// [`Response::follow_url_with`].
+    pub fn follow_url(&self, url: Url) -> Request {
+        self.follow_url_with(url, &[])
+    }
+
+    /// Creates a request like [`Response::follow_url`] that also carries
+    /// over the metadata stored under `keep_meta`, such as a category or a
+    /// priority.
+    ///
+    /// The depth and parent fingerprint are always set, whatever the keys.
+    pub fn follow_url_with(&self, url: Url, keep_meta: &[&str]) -> Request {
+        let depth = self.get_typed_meta(&DEPTH).unwrap_or(0) + 1;
+
+        let mut request = Request::new(url);
+        for key in keep_meta {
+            if let Some(value) = self.meta.get(*key) {
+                request
+                    .meta
+                    .insert(Cow::Owned((*key).to_owned()), value.value().clone());
+            }
+        }
+        if let Ok(referer) = HeaderValue::from_str(referer_for(&self.url).as_str()) {
+            request.headers.insert(REFERER, referer);
+        }
+        let request = request.with_depth(depth);
+        match &self.request_fingerprint {
+            Some(fingerprint) => request.with_parent_fingerprint(fingerprint.as_str()),
+            None => request,
+        }
+    }
+
+    /// Creates a request for `href`, resolved against this response.
+    ///
+    /// Relative URLs are resolved against the document's `<base href>` if it
+    /// has one, and against the response URL otherwise. The request is built
+    /// like [`Response::follow_url`], including the `Referer` header.
+    pub fn follow(&self, href: &str) -> Result<Request, SpiderError> {
+        self.follow_from(&self.base_url(), href, &[])
+    }
+
+    /// Creates a request for `href` like [`Response::follow`], carrying over
+    /// the metadata stored under `keep_meta` like
+    /// [`Response::follow_url_with`].
+    pub fn follow_with(&self, href: &str, keep_meta: &[&str]) -> Result<Request, SpiderError> {
+        self.follow_from(&self.base_url(), href, keep_meta)
+    }
+
+    /// Creates requests for each of `hrefs`, resolved like [`Response::follow`].
+    ///
+    /// Each href yields its own result, so one malformed link does not
+    /// prevent following the others.
+    pub fn follow_all<'a, I>(
+        &'a self,
+        hrefs: I,
+    ) -> impl Iterator<Item = Result<Request, SpiderError>> + 'a
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+        I::IntoIter: 'a,
+    {
+        let base = self.base_url();
+        hrefs
+            .into_iter()
+            .map(move |href| self.follow_from(&base, href.as_ref(), &[]))
+    }
+
+    /// Creates a request for a link extracted with [`Response::links`].
+    pub fn follow_link(&self, link: &Link) -> Request {
+        self.follow_url(link.url.clone())
+    }
+
+    /// Creates requests for links extracted with [`Response::links`].
+    pub fn follow_links<'a, I>(&'a self, links: I) -> impl Iterator<Item = Request> + 'a
+    where
+        I: IntoIterator<Item = Link>,
+        I::IntoIter: 'a,
+    {
+        links.into_iter().map(move |link| self.follow_url(link.url))
+    }
+
+    fn follow_from(
+        &self,
+        base: &Url,
+        href: &str,
+        keep_meta: &[&str],
+    ) -> Result<Request, SpiderError> {
+        let url = base.join(href.trim())?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(SpiderError::GeneralError(format!(
+                "Cannot follow URL with unsupported scheme: {}",
+                url
+            )));
+        }
+        Ok(self.follow_url_with(url, keep_meta))
+    }
+
+    /// Returns the URL relative links resolve against: the first valid
+    /// `<base href>` of the document (itself resolved against the response
+    /// URL), or the response URL.
+    pub fn base_url(&self) -> Url {
+        self.with_html(|html| document_base_url(html, &self.url))
+    }
+
+    /// Reads a metadata value, deserialized as `T`.
+    ///
+    /// Returns `None` if the key is missing or its value does not match `T`.
+    pub fn get_meta<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        meta::get(&self.meta, key)
+    }
+
+    /// Serializes `value` and stores it in the response's metadata.
+    pub fn set_meta<T: Serialize>(
+        &mut self,
+        key: impl Into<Cow<'static, str>>,
+        value: T,
+    ) -> Result<(), SpiderError> {
+        meta::set(&self.meta, key.into(), value)
+    }
+
+    /// Removes a metadata value, returning it.
+    pub fn remove_meta(&mut self, key: &str) -> Option<Value> {
+        meta::remove(&self.meta, key)
+    }
+
+    /// Reads the metadata value stored under a typed key.
+    pub fn get_typed_meta<T: DeserializeOwned>(&self, key: &MetaKey<T>) -> Option<T> {
+        meta::get(&self.meta, key.name())
+    }
+
+    /// Stores a metadata value under a typed key.
+    pub fn set_typed_meta<T: Serialize>(
+        &mut self,
+        key: &MetaKey<T>,
+        value: T,
+    ) -> Result<(), SpiderError> {
+        meta::set(&self.meta, Cow::Borrowed(key.name()), value)
+    }
+
+    /// Removes the metadata value stored under a typed key, returning it.
+    pub fn remove_typed_meta<T: DeserializeOwned>(&mut self, key: &MetaKey<T>) -> Option<T> {
+        meta::remove(&self.meta, key.name()).and_then(|v| serde_json::from_value(v).ok())
+    }
+
     /// Deserializes the response body as JSON.
     pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
         serde_json::from_slice(&self.body)
     }
 
-    /// Parses the response body as HTML.
+    /// Runs a JSONPath query against the JSON body and returns copies of the
+    /// selected values, see [`json_path`](crate::json_path).
+    ///
+    /// The body is parsed on every call. To run many queries, deserialize it
+    /// once with [`Response::json`] and use
+    /// [`json_path::query`](crate::json_path::query).
+    pub fn json_path(&self, expr: &str) -> Result<Vec<Value>, SpiderError> {
+        let path = get_cached_json_path(expr)?;
+        let value: Value = serde_json::from_str(&self.text())?;
+        Ok(path.query(&value).into_iter().cloned().collect())
+    }
+
+    /// Determines the character encoding of the body.
+    ///
+    /// In order of precedence: a byte order mark, the `charset` parameter of
+    /// the `Content-Type` header, a `<meta charset>` / `http-equiv` declaration
+    /// or XML `encoding` declaration near the start of the body, then UTF-8 if
+    /// the body is valid UTF-8 and windows-1252 otherwise.
+    pub fn encoding(&self) -> &'static Encoding {
+        if let Some((encoding, _)) = Encoding::for_bom(&self.body) {
+            return encoding;
+        }
+
+        if let Some(encoding) = self
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(charset_from_content_type)
+        {
+            return encoding;
+        }
+
+        if let Some(encoding) = sniff_declared_encoding(&self.body) {
+            return encoding;
+        }
+
+        if from_utf8(&self.body).is_ok() {
+            UTF_8
+        } else {
+            WINDOWS_1252
+        }
+    }
+
+    /// Decodes the body as text using the encoding found by [`Response::encoding`].
+    ///
+    /// Malformed sequences are replaced with U+FFFD. The body is borrowed when
+    /// it is already valid UTF-8.
+    pub fn text(&self) -> Cow<'_, str> {
+        let (text, _, _) = self.encoding().decode(&self.body);
+        text
+    }
+
+    /// Returns the body parsed as HTML, parsing it on first use.
+    pub(crate) fn document(&self) -> SharedDocument {
+        let encoding = self.encoding();
+        let parse = || Ok(Html::parse_document(&self.text()));
+        match DocumentCache::get_or_parse(&self.documents.html, &self.body, encoding, parse) {
+            Ok(document) => document,
+            Err(_) => Arc::new(ReentrantMutex::new(Html::parse_document(&self.text()))),
+        }
+    }
+
+    /// Runs `f` on the body parsed as HTML.
+    ///
+    /// The document is parsed on first use and shared by every helper of the
+    /// response, so this avoids the copy [`Response::to_html`] makes.
+    pub fn with_html<R>(&self, f: impl FnOnce(&Html) -> R) -> R {
+        let document = self.document();
+        let html = document.lock();
+        f(&html)
+    }
+
+    /// Returns a copy of the body parsed as HTML.
+    ///
+    /// The body is decoded with [`Response::text`] and parsed once, then
+    /// cached; the `Result` is kept for compatibility.
     pub fn to_html(&self) -> Result<Html, Utf8Error> {
-        let body_str = from_utf8(&self.body)?;
-        Ok(Html::parse_document(body_str))
+        Ok(self.with_html(Html::clone))
+    }
+
+    /// Drops the documents parsed from the body, so that the next helper
+    /// parses it again.
+    ///
+    /// Documents are reparsed on their own when the body or its encoding
+    /// changes, so this is only needed to release their memory early.
+    pub fn clear_parsed_documents(&self) {
+        self.documents.clear();
     }
 
     /// Lazily parses the response body as HTML, returning a closure that can be called when needed.
+    ///
+    /// The body is parsed at most once; each call returns a copy of the cached document.
     pub fn lazy_html(&self) -> Result<impl Fn() -> Result<Html, Utf8Error> + '_, Utf8Error> {
-        let body_bytes = &self.body;
-        Ok(move || {
-            let body_str = from_utf8(body_bytes)?;
-            Ok(Html::parse_document(body_str))
-        })
+        Ok(move || self.to_html())
+    }
+
+    /// Returns the robots directives that apply to `user_agent`.
+    ///
+    /// Combines `<meta name="robots">` and `<meta name="{user_agent}">` tags
+    /// with `X-Robots-Tag` headers, generic or addressed to `user_agent`.
+    /// Restrictions add up, so a directive set anywhere applies.
+    pub fn robots_directives(&self, user_agent: &str) -> RobotsDirectives {
+        self.with_html(|html| robots::page_directives(html, &self.headers, user_agent))
+    }
+
+    /// Returns a [`SelectorList`] holding the whole HTML document.
+    pub fn selector(&self) -> SelectorList {
+        SelectorList::from_document(self.document())
+    }
+
+    /// Runs a CSS query against the HTML body, see [`SelectorList::css`].
+    pub fn css(&self, query: &str) -> Result<SelectorList, SpiderError> {
+        self.selector().css(query)
+    }
+
+    /// Evaluates an XPath 1.0 expression against the body, see
+    /// [`SelectorList::xpath`].
+    ///
+    /// Bodies served with an XML media type, or starting with an XML
+    /// declaration when no type is given, are parsed as XML; others as HTML.
+    /// Returns [`SpiderError::XPathError`] if the expression is invalid and
+    /// [`SpiderError::XmlParseError`] if an XML body is malformed.
+    pub fn xpath(&self, expr: &str) -> Result<XPathValue, SpiderError> {
+        let xpath = get_cached_xpath(expr)?;
+        let document = if self.is_xml() {
+            let parse = || xpath::parse_xml(&self.text());
+            DocumentCache::get_or_parse(&self.documents.xml, &self.body, self.encoding(), parse)?
+        } else {
+            self.document()
+        };
+        xpath::evaluate(document, &xpath)
+    }
+
+    fn is_xml(&self) -> bool {
+        let media_type = self
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        if media_type.is_empty() {
+            let body = self
+                .body
+                .strip_prefix(b"\xEF\xBB\xBF")
+                .unwrap_or(&self.body);
+            return body.trim_ascii_start().starts_with(b"<?xml");
+        }
+        (media_type.ends_with("/xml") || media_type.ends_with("+xml"))
+            && media_type != "application/xhtml+xml"
+    }
+
+    /// Extracts the JSON-LD, Microdata, OpenGraph, Twitter card and RDFa Lite
+    /// data embedded in the HTML page.
+    pub fn structured_data(&self) -> StructuredData {
+        self.with_html(|html| StructuredData::extract(html, &document_base_url(html, &self.url)))
+    }
+
+    /// Extracts the application state embedded in the scripts of the HTML
+    /// page, such as `__NEXT_DATA__` or `window.__INITIAL_STATE__`, keyed by
+    /// name, see [`embedded_json`](crate::embedded_json).
+    pub fn embedded_json(&self) -> Map<String, Value> {
+        self.with_html(embedded_json::extract)
+    }
+
+    /// Lays out every `<table>` of the HTML page, with row and column spans
+    /// expanded, see [`table`](crate::table).
+    pub fn tables(&self) -> Vec<Table> {
+        self.with_html(table::extract)
+    }
+
+    /// Parses the body as an RSS 2.0, RSS 1.0 (RDF) or Atom feed.
+    pub fn feed(&self) -> Result<Feed, SpiderError> {
+        Feed::parse(&self.text(), &self.url)
+    }
+
+    /// Returns the feeds the HTML page advertises with
+    /// `<link rel="alternate">` elements, in document order.
+    pub fn feed_links(&self) -> Vec<FeedLink> {
+        self.with_html(|html| feed::feed_links(html, &document_base_url(html, &self.url)))
+    }
+
+    /// Parses the body as a sitemap, sitemap index or plain-text sitemap,
+    /// gzipped or not, and streams its entries.
+    pub fn sitemap(&self) -> SitemapParser<'_> {
+        SitemapParser::from_response(self)
     }
 
     /// Extracts all unique, same-site links from the response body.
+    ///
+    /// This is a preset of [`LinkExtractor`]; use it directly to filter or
+    /// order the links.
     pub fn links(&self) -> DashSet<Link> {
-        let links = DashSet::new();
+        LinkExtractor::new().extract(self).into_iter().collect()
+    }
+}
 
-        if let Ok(html_fn) = self.lazy_html()
-            && let Ok(html) = html_fn()
-        {
-            let selectors = vec![
-                ("a[href]", "href"),
-                ("link[href]", "href"),
-                ("script[src]", "src"),
-                ("img[src]", "src"),
-                ("audio[src]", "src"),
-                ("video[src]", "src"),
-                ("source[src]", "src"),
-            ];
-
-            for (selector_str, attr_name) in selectors {
-                if let Some(selector) = get_cached_selector(selector_str) {
-                    for element in html.select(&selector) {
-                        if let Some(attr_value) = element.value().attr(attr_name)
-                            && let Ok(url) = self.url.join(attr_value)
-                            && utils::is_same_site(&url, &self.url)
-                        {
-                            let link_type = match element.value().name() {
-                                "a" => LinkType::Page,
-                                "link" => {
-                                    if let Some(rel) = element.value().attr("rel") {
-                                        if rel.eq_ignore_ascii_case("stylesheet") {
-                                            LinkType::Stylesheet
-                                        } else {
-                                            LinkType::Other(rel.to_string())
-                                        }
-                                    } else {
-                                        LinkType::Other("link".to_string())
-                                    }
-                                }
-                                "script" => LinkType::Script,
-                                "img" => LinkType::Image,
-                                "audio" | "video" | "source" => LinkType::Media,
-                                _ => LinkType::Other(element.value().name().to_string()),
-                            };
-                            links.insert(Link { url, link_type });
-                        }
-                    }
-                }
+/// Returns the effective base URL of a document served from `url`.
+pub(crate) fn document_base_url(html: &Html, url: &Url) -> Url {
+    if let Some(selector) = get_cached_selector("base[href]") {
+        for element in html.select(&selector) {
+            if let Some(href) = element.value().attr("href")
+                && let Ok(base) = url.join(href.trim())
+            {
+                return base;
             }
+        }
+    }
+    url.clone()
+}
 
-            let finder = LinkFinder::new();
-            for text_node in html.tree.values().filter_map(|node| node.as_text()) {
-                for link in finder.links(text_node) {
-                    if link.kind() == &LinkKind::Url
-                        && let Ok(url) = self.url.join(link.as_str())
-                        && utils::is_same_site(&url, &self.url)
-                    {
-                        links.insert(Link {
-                            url,
-                            link_type: LinkType::Page,
-                        });
-                    }
-                }
-            }
+/// Extracts the encoding named by the `charset` parameter of a `Content-Type` value.
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
         }
+        let label = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        Encoding::for_label(label.as_bytes())
+    })
+}
+
+/// Looks for an encoding declared in the first 1024 bytes of a document,
+/// either in an XML declaration or in a `<meta>` tag (`charset` attribute or
+/// `http-equiv` content).
+fn sniff_declared_encoding(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..body.len().min(1024)];
+    let head = head.to_ascii_lowercase();
+
+    if head.starts_with(b"<?xml")
+        && let Some(end) = find_bytes(&head, b"?>")
+        && let Some(label) = attribute_value_after(&head[..end], b"encoding=")
+        && let Some(encoding) = Encoding::for_label(label)
+    {
+        return Some(encoding.output_encoding());
+    }
 
-        links
+    let mut rest = &head[..];
+    while let Some(start) = find_bytes(rest, b"<meta") {
+        let tag = &rest[start..];
+        let tag = &tag[..find_bytes(tag, b">").unwrap_or(tag.len())];
+        if let Some(label) = attribute_value_after(tag, b"charset=")
+            && let Some(encoding) = Encoding::for_label(label)
+        {
+            // A document that claims UTF-16 here cannot be UTF-16, as the
+            // declaration was readable as ASCII.
+            return Some(encoding.output_encoding());
+        }
+        rest = &rest[start + tag.len()..];
     }
 
+    None
+}
+
+/// Returns the value following `prefix` in `haystack`, up to the next quote,
+/// whitespace, `;` or `>`.
+fn attribute_value_after<'a>(haystack: &'a [u8], prefix: &[u8]) -> Option<&'a [u8]> {
+    let start = find_bytes(haystack, prefix)? + prefix.len();
+    let value = &haystack[start..];
+    let value = value
+        .strip_prefix(b"\"")
+        .or_else(|| value.strip_prefix(b"'"))
+        .unwrap_or(value);
+    let end = value
+        .iter()
+        .position(|&b| matches!(b, b'"' | b'\'' | b';' | b'>' | b'/') || b.is_ascii_whitespace())
+        .unwrap_or(value.len());
+    (end > 0).then(|| &value[..end])
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Returns `url` without its fragment and credentials, as sent in a `Referer` header.
+fn referer_for(url: &Url) -> Url {
+    let mut referer = url.clone();
+    referer.set_fragment(None);
+    let _ = referer.set_username("");
+    let _ = referer.set_password(None);
+    referer
 }
 
 impl Clone for Response {
@@ -178,6 +718,127 @@ impl Clone for Response {
             request_url: self.request_url.clone(),
             meta: self.meta.clone(),
             cached: self.cached,
+            request_fingerprint: self.request_fingerprint.clone(),
+            documents: self.documents.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::CanonicalizeOptions;
+    use reqwest::Method;
+    use serde_json::json;
+
+    /// The response to `request`, as a downloader would build it after
+    /// fingerprinting the request.
+    fn response_to(request: &Request, body: &str) -> Response {
+        let mut response = Response::new(
+            request.url.clone(),
+            StatusCode::OK,
+            HeaderMap::new(),
+            Bytes::from(body.to_owned()),
+        )
+        .with_request_fingerprint(request.fingerprint());
+        response.meta = request.meta.clone();
+        response
+    }
+
+    #[test]
+    fn follow_url_records_the_fingerprint_of_the_sent_request() {
+        let request = Request::new(Url::parse("https://example.com/search").unwrap())
+            .with_method(Method::POST)
+            .with_json(json!({ "query": "books" }));
+        let fingerprint = request.fingerprint();
+
+        let response = response_to(&request, "");
+        let next = response.follow_url(Url::parse("https://example.com/page/2").unwrap());
+
+        assert_eq!(next.get_parent_fingerprint(), Some(fingerprint));
+        assert_eq!(next.get_depth(), 1);
+    }
+
+    #[test]
+    fn fingerprinting_leaves_the_request_unchanged() {
+        let request = Request::new(Url::parse("https://example.com/").unwrap());
+        request.fingerprint();
+        request.canonical_fingerprint(&CanonicalizeOptions::default());
+        assert!(request.meta.is_empty());
+    }
+
+    #[test]
+    fn follow_carries_over_only_the_listed_meta() {
+        let request = Request::new(Url::parse("https://example.com/").unwrap())
+            .with_priority(5)
+            .with_depth(2)
+            .with_meta("category", json!("books"))
+            .with_meta("retry_attempts", json!(3));
+        let response = response_to(&request, "");
+
+        let next = response.follow("/a").unwrap();
+        assert_eq!(next.get_depth(), 3);
+        assert!(next.get_parent_fingerprint().is_some());
+        let mut keys: Vec<String> = next.meta.iter().map(|e| e.key().to_string()).collect();
+        keys.sort();
+        assert_eq!(keys, ["depth", "parent_fingerprint"]);
+
+        let next = response
+            .follow_with("/a", &["category", "missing"])
+            .unwrap();
+        assert_eq!(
+            next.get_meta::<String>("category").as_deref(),
+            Some("books")
+        );
+        assert_eq!(next.get_priority(), 0);
+        assert_eq!(next.meta.len(), 3);
+    }
+
+    #[test]
+    fn links_built_by_hand_have_an_unknown_source() {
+        let link = Link::new(Url::parse("https://example.com/").unwrap(), LinkType::Page);
+        assert_eq!(link.source, LinkSource::Unknown);
+        assert!(!link.is_from_text());
+
+        let response = Response::new(
+            Url::parse("https://example.com/").unwrap(),
+            StatusCode::OK,
+            HeaderMap::new(),
+            Bytes::from_static(b"<p>See https://example.com/page for more.</p>"),
+        );
+        let links = LinkExtractor::new().text_links(true).extract(&response);
+        assert!(links.iter().any(|link| link.is_from_text()));
+    }
+
+    #[test]
+    fn follow_url_without_a_fingerprint_has_no_parent() {
+        let response = Response::new(
+            Url::parse("https://example.com/").unwrap(),
+            StatusCode::OK,
+            HeaderMap::new(),
+            Bytes::new(),
+        );
+        let next = response.follow_url(Url::parse("https://example.com/a").unwrap());
+        assert_eq!(next.get_parent_fingerprint(), None);
+    }
+
+    #[test]
+    fn documents_are_reparsed_when_the_charset_changes() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let body = Bytes::from_static(b"<p>caf\xe9</p>");
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        let mut response = Response::new(url, StatusCode::OK, headers, body);
+        let text = |response: &Response| response.css("p::text").unwrap().get();
+        assert_eq!(text(&response).as_deref(), Some("caf\u{fffd}"));
+
+        response.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=windows-1252"),
+        );
+        assert_eq!(text(&response).as_deref(), Some("café"));
+    }
+}
@@ -0,0 +1,799 @@
+//! # Robots Module
+//!
+//! Implements the robots exclusion rules a polite crawler has to honour.
+//!
+//! ## Overview
+//!
+//! - `RobotsTxt` parses a site's robots.txt (RFC 9309) and decides whether a
+//!   request may be crawled, using user-agent groups, `Allow`/`Disallow`
+//!   rules with `*` and `$` wildcards and longest-match precedence. It also
+//!   exposes `Crawl-delay` and `Sitemap` lines.
+//! - `RobotsCache` keeps one parsed robots.txt per origin.
+//! - Pages can restrict what crawlers do with them through
+//!   `<meta name="robots">` tags and `X-Robots-Tag` response headers, either
+//!   for all crawlers or for a specific one (`<meta name="mybot">`,
+//!   `X-Robots-Tag: mybot: noindex`). `RobotsDirectives` is the parsed,
+//!   merged result of those rules for one user agent.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use spider_util::robots::{RobotsCache, RobotsTxt, robots_txt_url};
+//!
+//! let cache = RobotsCache::new();
+//! if cache.get(&request).is_none() {
+//!     // Fetch robots_txt_url(&request), then:
+//!     cache.insert(&request, RobotsTxt::from_response(&robots_response));
+//! }
+//! cache.check(&request, "mybot")?;
+//!
+//! let directives = response.robots_directives("mybot");
+//! if !directives.nofollow {
+//!     // Follow its links
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use http::header::HeaderMap;
+use scraper::Html;
+use url::Url;
+
+use crate::error::SpiderError;
+use crate::request::Request;
+use crate::response::Response;
+use crate::selector_cache::get_cached_selector;
+use crate::utils::{normalize_origin, normalize_percent_encoding};
+
+/// The amount of a robots.txt body that is parsed; the rest is ignored
+/// (RFC 9309, section 2.5).
+pub const MAX_ROBOTS_TXT_SIZE: usize = 500 * 1024;
+
+/// How long `RobotsCache` keeps a robots.txt by default (RFC 9309, section 2.4).
+pub const DEFAULT_ROBOTS_TXT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Returns the URL of the robots.txt that governs `request`.
+pub fn robots_txt_url(request: &Request) -> Url {
+    let mut url = request.url.clone();
+    url.set_path("/robots.txt");
+    url.set_query(None);
+    url.set_fragment(None);
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url
+}
+
+/// A parsed robots.txt file.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+    sitemaps: Vec<String>,
+    disallow_all: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl RobotsTxt {
+    /// Parses a robots.txt body.
+    ///
+    /// Only the first [`MAX_ROBOTS_TXT_SIZE`] bytes are read; invalid lines
+    /// are skipped.
+    pub fn parse(body: &[u8]) -> Self {
+        let body = if body.len() > MAX_ROBOTS_TXT_SIZE {
+            // Drop the line cut in half by the limit.
+            let truncated = &body[..MAX_ROBOTS_TXT_SIZE];
+            let end = truncated
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .unwrap_or(MAX_ROBOTS_TXT_SIZE);
+            &body[..end]
+        } else {
+            body
+        };
+        let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+        let text = String::from_utf8_lossy(body);
+
+        let mut robots = Self::default();
+        let mut current: Option<Group> = None;
+        let mut in_agent_lines = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agent_lines {
+                        robots.groups.extend(current.take());
+                        current = Some(Group::default());
+                        in_agent_lines = true;
+                    }
+                    if let Some(group) = current.as_mut() {
+                        group.user_agents.push(user_agent_token(value));
+                    }
+                }
+                "allow" | "disallow" => {
+                    in_agent_lines = false;
+                    // An empty `Disallow` allows everything, like no rule at all.
+                    if let Some(group) = current.as_mut()
+                        && !value.is_empty()
+                    {
+                        group.rules.push(Rule {
+                            allow: key.trim().eq_ignore_ascii_case("allow"),
+                            pattern: encode_pattern(value),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_agent_lines = false;
+                    if let Some(group) = current.as_mut()
+                        && let Ok(seconds) = value.parse::<f64>()
+                        && seconds.is_finite()
+                        && seconds >= 0.0
+                    {
+                        group.crawl_delay = Some(Duration::from_secs_f64(seconds));
+                    }
+                }
+                "sitemap" if !value.is_empty() => robots.sitemaps.push(value.to_owned()),
+                _ => {}
+            }
+        }
+        robots.groups.extend(current);
+
+        robots
+    }
+
+    /// Interprets a robots.txt fetch: successful responses are parsed, other
+    /// client errors allow everything and server errors or `429 Too Many
+    /// Requests` disallow everything (RFC 9309, section 2.3.1).
+    pub fn from_response(response: &Response) -> Self {
+        let status = response.status;
+        if status.is_success() {
+            Self::parse(&response.body)
+        } else if status.is_server_error() || status.as_u16() == 429 {
+            Self::disallow_all()
+        } else {
+            Self::allow_all()
+        }
+    }
+
+    /// A robots.txt that allows everything, used when none exists.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// A robots.txt that disallows everything, used when it is unreachable.
+    pub fn disallow_all() -> Self {
+        Self {
+            disallow_all: true,
+            ..Self::default()
+        }
+    }
+
+    /// Returns `true` if `user_agent` may crawl the request's URL.
+    pub fn is_allowed(&self, request: &Request, user_agent: &str) -> bool {
+        self.is_url_allowed(&request.url, user_agent)
+    }
+
+    /// Returns `true` if `user_agent` may crawl `url`.
+    ///
+    /// The robots.txt file itself is always allowed.
+    pub fn is_url_allowed(&self, url: &Url, user_agent: &str) -> bool {
+        let mut path = url.path().to_owned();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        let path = normalize_percent_encoding(&path);
+        if path == "/robots.txt" {
+            return true;
+        }
+        if self.disallow_all {
+            return false;
+        }
+
+        // The longest matching pattern wins; on a tie, `Allow` does.
+        let mut best: Option<(usize, bool)> = None;
+        for rule in self.groups_for(user_agent).flat_map(|group| &group.rules) {
+            if pattern_matches(&rule.pattern, &path) {
+                let len = rule.pattern.len();
+                best = match best {
+                    Some((best_len, best_allow))
+                        if best_len > len || (best_len == len && (best_allow || !rule.allow)) =>
+                    {
+                        Some((best_len, best_allow))
+                    }
+                    _ => Some((len, rule.allow)),
+                };
+            }
+        }
+
+        best.is_none_or(|(_, allow)| allow)
+    }
+
+    /// Returns [`SpiderError::BlockedByRobotsTxt`] if `user_agent` may not
+    /// crawl the request's URL.
+    pub fn check(&self, request: &Request, user_agent: &str) -> Result<(), SpiderError> {
+        if self.is_allowed(request, user_agent) {
+            Ok(())
+        } else {
+            Err(SpiderError::BlockedByRobotsTxt)
+        }
+    }
+
+    /// Returns the `Crawl-delay` that applies to `user_agent`, if any.
+    pub fn crawl_delay(&self, user_agent: &str) -> Option<Duration> {
+        self.groups_for(user_agent)
+            .filter_map(|group| group.crawl_delay)
+            .max()
+    }
+
+    /// Returns the URLs of the `Sitemap` lines, as written.
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+
+    /// Returns the groups addressed to `user_agent`, or else the `*` groups.
+    fn groups_for<'a>(&'a self, user_agent: &str) -> impl Iterator<Item = &'a Group> + 'a {
+        let agent = user_agent_token(user_agent);
+        let specific = !agent.is_empty()
+            && self
+                .groups
+                .iter()
+                .any(|group| group.user_agents.contains(&agent));
+        let target = if specific { agent } else { "*".to_owned() };
+        self.groups
+            .iter()
+            .filter(move |group| group.user_agents.contains(&target))
+    }
+}
+
+/// Percent-encodes the non-ASCII characters of a rule pattern and normalizes
+/// its escapes, so it compares byte for byte with URL paths.
+fn encode_pattern(pattern: &str) -> String {
+    let mut encoded = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if c.is_ascii() {
+            encoded.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                encoded.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+    normalize_percent_encoding(&encoded)
+}
+
+/// Matches a rule pattern against the start of `path`, where `*` matches any
+/// sequence of characters and a trailing `$` anchors the end.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern.as_bytes(), true),
+        None => (pattern.as_bytes(), false),
+    };
+    let path = path.as_bytes();
+
+    let (mut p, mut s) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    loop {
+        if p == pattern.len() && (!anchored || s == path.len()) {
+            return true;
+        }
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, s));
+            p += 1;
+        } else if p < pattern.len() && s < path.len() && pattern[p] == path[s] {
+            p += 1;
+            s += 1;
+        } else if let Some((star, matched)) = backtrack
+            && matched < path.len()
+        {
+            // Let the last `*` absorb one more character and retry.
+            backtrack = Some((star, matched + 1));
+            p = star + 1;
+            s = matched + 1;
+        } else {
+            return false;
+        }
+    }
+}
+
+/// A cache of parsed robots.txt files, one per origin.
+///
+/// Entries expire after a time-to-live, 24 hours by default.
+#[derive(Debug)]
+pub struct RobotsCache {
+    entries: DashMap<String, (Arc<RobotsTxt>, Instant)>,
+    ttl: Duration,
+}
+
+impl Default for RobotsCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RobotsCache {
+    /// Creates an empty cache with the default time-to-live.
+    pub fn new() -> Self {
+        Self::with_ttl(DEFAULT_ROBOTS_TXT_TTL)
+    }
+
+    /// Creates an empty cache whose entries expire after `ttl`.
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Returns the robots.txt of the request's origin, if cached and fresh.
+    pub fn get(&self, request: &Request) -> Option<Arc<RobotsTxt>> {
+        let key = normalize_origin(request);
+        let entry = self.entries.get(&key)?;
+        let (robots, fetched_at) = entry.value();
+        if fetched_at.elapsed() < self.ttl {
+            Some(Arc::clone(robots))
+        } else {
+            drop(entry);
+            self.entries.remove(&key);
+            None
+        }
+    }
+
+    /// Stores the robots.txt of the request's origin.
+    pub fn insert(&self, request: &Request, robots: RobotsTxt) -> Arc<RobotsTxt> {
+        let robots = Arc::new(robots);
+        self.entries.insert(
+            normalize_origin(request),
+            (Arc::clone(&robots), Instant::now()),
+        );
+        robots
+    }
+
+    /// Removes the robots.txt of the request's origin.
+    pub fn remove(&self, request: &Request) -> Option<Arc<RobotsTxt>> {
+        self.entries
+            .remove(&normalize_origin(request))
+            .map(|(_, (robots, _))| robots)
+    }
+
+    /// Returns `Some(true)` if `user_agent` may crawl the request, or `None`
+    /// if the robots.txt of its origin is not cached.
+    pub fn is_allowed(&self, request: &Request, user_agent: &str) -> Option<bool> {
+        self.get(request)
+            .map(|robots| robots.is_allowed(request, user_agent))
+    }
+
+    /// Returns [`SpiderError::BlockedByRobotsTxt`] if the cached robots.txt of
+    /// the request's origin disallows it. Uncached origins are allowed.
+    pub fn check(&self, request: &Request, user_agent: &str) -> Result<(), SpiderError> {
+        match self.get(request) {
+            Some(robots) => robots.check(request, user_agent),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the number of cached origins, including expired ones.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no origin is cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every cached robots.txt.
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+/// The directives that apply to a page for a given crawler.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RobotsDirectives {
+    /// The page must not be indexed.
+    pub noindex: bool,
+    /// The links on the page must not be followed.
+    pub nofollow: bool,
+    /// No cached copy of the page may be kept.
+    pub noarchive: bool,
+    /// No snippet of the page may be shown.
+    pub nosnippet: bool,
+    /// Images on the page must not be indexed.
+    pub noimageindex: bool,
+    /// The date after which the page must no longer be shown, as written.
+    pub unavailable_after: Option<String>,
+}
+
+const KNOWN_DIRECTIVES: &[&str] = &[
+    "all",
+    "none",
+    "index",
+    "noindex",
+    "follow",
+    "nofollow",
+    "noarchive",
+    "nocache",
+    "nosnippet",
+    "noimageindex",
+    "notranslate",
+    "indexifembedded",
+    "max-snippet",
+    "max-image-preview",
+    "max-video-preview",
+    "unavailable_after",
+];
+
+impl RobotsDirectives {
+    /// Parses a comma-separated directive list, such as the `content` of a
+    /// robots meta tag. Unknown directives are ignored.
+    pub fn parse(content: &str) -> Self {
+        let mut directives = Self::default();
+        directives.apply(content);
+        directives
+    }
+
+    /// Returns `true` if no restriction applies.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Adds the restrictions of `other` to these directives.
+    pub fn merge(&mut self, other: &RobotsDirectives) {
+        self.noindex |= other.noindex;
+        self.nofollow |= other.nofollow;
+        self.noarchive |= other.noarchive;
+        self.nosnippet |= other.nosnippet;
+        self.noimageindex |= other.noimageindex;
+        if other.unavailable_after.is_some() {
+            self.unavailable_after = other.unavailable_after.clone();
+        }
+    }
+
+    fn apply(&mut self, content: &str) {
+        let mut tokens = content.split(',').peekable();
+        while let Some(token) = tokens.next() {
+            let token = token.trim();
+            let name = directive_name(token);
+            match name.as_str() {
+                "none" => {
+                    self.noindex = true;
+                    self.nofollow = true;
+                }
+                "noindex" => self.noindex = true,
+                "nofollow" => self.nofollow = true,
+                "noarchive" | "nocache" => self.noarchive = true,
+                "nosnippet" => self.nosnippet = true,
+                "noimageindex" => self.noimageindex = true,
+                "unavailable_after" => {
+                    // Dates such as RFC 850 ones contain commas, so keep
+                    // consuming tokens until the next known directive.
+                    let mut date = token
+                        .split_once(':')
+                        .map(|(_, date)| date.trim().to_owned())
+                        .unwrap_or_default();
+                    while let Some(next) = tokens.peek() {
+                        if KNOWN_DIRECTIVES.contains(&directive_name(next.trim()).as_str()) {
+                            break;
+                        }
+                        date.push(',');
+                        date.push_str(next);
+                        tokens.next();
+                    }
+                    let date = date.trim();
+                    if !date.is_empty() {
+                        self.unavailable_after = Some(date.to_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Returns the lowercased name of a directive token (`max-snippet:50` -> `max-snippet`).
+fn directive_name(token: &str) -> String {
+    token
+        .split(':')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Reduces a user agent to its lowercased product token (`MyBot/1.0` -> `mybot`).
+pub(crate) fn user_agent_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Collects the directives for `user_agent` from a page's robots meta tags
+/// and `X-Robots-Tag` headers.
+pub(crate) fn page_directives(
+    html: &Html,
+    headers: &HeaderMap,
+    user_agent: &str,
+) -> RobotsDirectives {
+    let agent = user_agent_token(user_agent);
+    let mut directives = RobotsDirectives::default();
+
+    if let Some(selector) = get_cached_selector("meta[name][content]") {
+        for element in html.select(&selector) {
+            let name = element.value().attr("name").unwrap_or_default().trim();
+            if name.eq_ignore_ascii_case("robots")
+                || (!agent.is_empty() && name.eq_ignore_ascii_case(&agent))
+            {
+                let content = element.value().attr("content").unwrap_or_default();
+                directives.merge(&RobotsDirectives::parse(content));
+            }
+        }
+    }
+
+    for value in headers.get_all("x-robots-tag") {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        // `X-Robots-Tag: mybot: noindex` targets a single crawler. The prefix
+        // is a user agent only if it is a single token that is not a
+        // directive, unlike in `noindex, max-snippet: 50`.
+        match value.split_once(':') {
+            Some((prefix, rest)) if is_user_agent_prefix(prefix.trim()) => {
+                if prefix.trim().eq_ignore_ascii_case(&agent) {
+                    directives.merge(&RobotsDirectives::parse(rest));
+                }
+            }
+            _ => directives.merge(&RobotsDirectives::parse(value)),
+        }
+    }
+
+    directives
+}
+
+fn is_user_agent_prefix(prefix: &str) -> bool {
+    !prefix.is_empty()
+        && !prefix.contains(|c: char| c == ',' || c.is_whitespace())
+        && !KNOWN_DIRECTIVES.contains(&prefix.to_ascii_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_directives(value: &str, user_agent: &str) -> RobotsDirectives {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-robots-tag", value.parse().unwrap());
+        page_directives(&Html::parse_document(""), &headers, user_agent)
+    }
+
+    #[test]
+    fn header_directives_with_values_apply_to_every_agent() {
+        let directives = header_directives("noindex, max-snippet:50", "mybot");
+        assert!(directives.noindex);
+
+        let directives = header_directives(
+            "noindex, unavailable_after: 25 Jun 2010 15:00:00 PST",
+            "mybot",
+        );
+        assert!(directives.noindex);
+        assert_eq!(
+            directives.unavailable_after.as_deref(),
+            Some("25 Jun 2010 15:00:00 PST")
+        );
+
+        let directives = header_directives("max-snippet: 50, nofollow", "mybot");
+        assert!(directives.nofollow);
+    }
+
+    #[test]
+    fn header_directives_for_a_user_agent() {
+        assert!(header_directives("mybot: noindex", "MyBot/1.0").noindex);
+        assert!(!header_directives("otherbot: noindex", "MyBot/1.0").noindex);
+        assert!(header_directives("noindex", "MyBot/1.0").noindex);
+    }
+
+    fn allowed(robots: &RobotsTxt, path: &str, user_agent: &str) -> bool {
+        let url = Url::parse("https://example.com")
+            .unwrap()
+            .join(path)
+            .unwrap();
+        robots.is_url_allowed(&url, user_agent)
+    }
+
+    fn response_with_status(status: u16, body: &'static str) -> Response {
+        Response::new(
+            Url::parse("https://example.com/robots.txt").unwrap(),
+            http::StatusCode::from_u16(status).unwrap(),
+            HeaderMap::new(),
+            bytes::Bytes::from_static(body.as_bytes()),
+        )
+    }
+
+    #[test]
+    fn wildcard_patterns() {
+        // (pattern, path, matches), after RFC 9309, section 2.2.3.
+        let cases = [
+            ("/fish", "/fish", true),
+            ("/fish", "/fish.html", true),
+            ("/fish", "/fishheads/yummy.html", true),
+            ("/fish", "/Fish.asp", false),
+            ("/fish", "/catfish", false),
+            ("/fish*", "/fish", true),
+            ("/fish*", "/fishheads", true),
+            ("/fish/", "/fish/salmon.htm", true),
+            ("/fish/", "/fish", false),
+            ("/fish*.php", "/fish.php", true),
+            ("/fish*.php", "/fishheads/catfish.php?parameters", true),
+            ("/fish*.php", "/Fish.PHP", false),
+            ("/*.php", "/filename.php", true),
+            ("/*.php", "/folder/filename.php?parameters", true),
+            ("/*.php", "/windows.PHP", false),
+            ("/*.php$", "/filename.php", true),
+            ("/*.php$", "/folder/filename.php", true),
+            ("/*.php$", "/filename.php?parameters", false),
+            ("/*.php$", "/filename.php/", false),
+            ("/*.php$", "/filename.php5", false),
+            ("/*.pdf$", "/files/report.pdf", true),
+            ("/*.pdf$", "/files/report.pdf.html", false),
+            ("/$", "/", true),
+            ("/$", "/page", false),
+            ("*", "/anything", true),
+            ("/a*b*c", "/aXXbYYc", true),
+            ("/a*b*c", "/aXXcYYb", false),
+        ];
+        for (pattern, path, matches) in cases {
+            assert_eq!(
+                pattern_matches(pattern, path),
+                matches,
+                "{pattern} against {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn longest_match_wins_and_allow_wins_ties() {
+        // (rules, path, allowed), after RFC 9309, section 5.2.
+        let cases = [
+            ("allow: /p\ndisallow: /", "/page", true),
+            ("allow: /folder\ndisallow: /folder", "/folder/page", true),
+            ("allow: /page\ndisallow: /*.htm", "/page.htm", false),
+            ("allow: /page\ndisallow: /*.ph", "/page.php5", true),
+            ("allow: /$\ndisallow: /", "/", true),
+            ("allow: /$\ndisallow: /", "/page.htm", false),
+            ("disallow: /private", "/private/data", false),
+            ("disallow: /private", "/public", true),
+            ("disallow:", "/anything", true),
+            ("disallow: /ü", "/ü", false),
+            ("disallow: /search?q=", "/search?q=books", false),
+        ];
+        for (rules, path, expected) in cases {
+            let robots = RobotsTxt::parse(format!("user-agent: *\n{rules}\n").as_bytes());
+            assert_eq!(
+                allowed(&robots, path, "mybot"),
+                expected,
+                "{rules:?} for {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn groups_are_selected_by_user_agent() {
+        let robots = RobotsTxt::parse(
+            b"\xEF\xBB\xBFUser-agent: *\n\
+              Disallow: /\n\
+              \n\
+              User-agent: MyBot\n\
+              User-agent: otherbot\n\
+              Disallow: /private # no comment\n\
+              Crawl-delay: 2\n\
+              \n\
+              user-agent: mybot\n\
+              disallow: /tmp\n\
+              crawl-delay: 5.5\n\
+              \n\
+              Sitemap: https://example.com/sitemap.xml\n\
+              invalid line\n",
+        );
+
+        assert!(allowed(&robots, "/page", "MyBot/1.0"));
+        assert!(!allowed(&robots, "/private/a", "MyBot/1.0"));
+        assert!(!allowed(&robots, "/tmp/a", "MyBot/1.0"));
+        assert!(allowed(&robots, "/tmp/a", "otherbot"));
+        assert!(!allowed(&robots, "/page", "thirdbot"));
+        assert!(allowed(&robots, "/robots.txt", "thirdbot"));
+
+        assert_eq!(
+            robots.crawl_delay("mybot"),
+            Some(Duration::from_secs_f64(5.5))
+        );
+        assert_eq!(robots.crawl_delay("otherbot"), Some(Duration::from_secs(2)));
+        assert_eq!(robots.crawl_delay("thirdbot"), None);
+        assert_eq!(robots.sitemaps(), ["https://example.com/sitemap.xml"]);
+    }
+
+    #[test]
+    fn only_the_first_500_kib_are_parsed() {
+        let mut body = String::from("user-agent: *\ndisallow: /early\n");
+        while body.len() < MAX_ROBOTS_TXT_SIZE - 10 {
+            body.push_str("# padding\n");
+        }
+        // This line straddles the limit, the next one is past it.
+        body.push_str("disallow: /straddling\ndisallow: /late\n");
+        assert!(body.len() > MAX_ROBOTS_TXT_SIZE);
+
+        let robots = RobotsTxt::parse(body.as_bytes());
+        assert!(!allowed(&robots, "/early", "mybot"));
+        assert!(allowed(&robots, "/straddling", "mybot"));
+        assert!(allowed(&robots, "/late", "mybot"));
+    }
+
+    #[test]
+    fn fetch_status_decides_unavailable_robots_txt() {
+        // (status, allowed)
+        let cases = [
+            (200, false),
+            (301, true),
+            (401, true),
+            (403, true),
+            (404, true),
+            (429, false),
+            (500, false),
+            (503, false),
+        ];
+        for (status, expected) in cases {
+            let response = response_with_status(status, "user-agent: *\ndisallow: /\n");
+            let robots = RobotsTxt::from_response(&response);
+            assert_eq!(
+                allowed(&robots, "/page", "mybot"),
+                expected,
+                "status {status}"
+            );
+            assert!(allowed(&robots, "/robots.txt", "mybot"));
+        }
+    }
+
+    #[test]
+    fn cache_entries_expire() {
+        let request = Request::new(Url::parse("https://example.com/private").unwrap());
+        let robots = || RobotsTxt::parse(b"user-agent: *\ndisallow: /private\n");
+
+        let cache = RobotsCache::new();
+        assert_eq!(cache.is_allowed(&request, "mybot"), None);
+        assert!(cache.check(&request, "mybot").is_ok());
+        cache.insert(&request, robots());
+        assert_eq!(cache.is_allowed(&request, "mybot"), Some(false));
+        assert!(matches!(
+            cache.check(&request, "mybot"),
+            Err(SpiderError::BlockedByRobotsTxt)
+        ));
+        let other_origin = Request::new(Url::parse("https://example.org/private").unwrap());
+        assert!(cache.get(&other_origin).is_none());
+
+        let cache = RobotsCache::with_ttl(Duration::ZERO);
+        cache.insert(&request, robots());
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&request).is_none());
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,491 @@
+//! # Selector Module
+//!
+//! A chainable, Parsel-style selection API over parsed HTML documents.
+//!
+//! ## Overview
+//!
+//! `SelectorList` holds a set of matches in a document that is parsed once
+//! and shared by every list derived from it. Lists can be refined with
+//! further CSS queries and turned into strings:
+//! - `css` runs a CSS query below each matched element. A query may end with
+//!   the `::text` or `::attr(name)` pseudo-elements to select text nodes or
+//!   attribute values instead of elements.
+//! - `xpath` evaluates an XPath 1.0 expression relative to each match.
+//! - `text` and `attr` select the text nodes or an attribute of the matches.
+//! - `get` and `get_all` return the matches as strings: the outer HTML of
+//!   elements, or the selected text.
+//! - `re` and `re_first` apply a regular expression to those strings.
+//!
+//! CSS selectors are compiled through [`selector_cache`](crate::selector_cache).
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! for product in response.css("div.product")?.iter() {
+//!     let name = product.css("h2::text")?.get();
+//!     let href = product.css("a::attr(href)")?.get();
+//!     let price = product.css(".price::text")?.re_first(r"[\d.]+")?;
+//! }
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use ego_tree::NodeId;
+use parking_lot::ReentrantMutex;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node};
+
+use crate::error::SpiderError;
+use crate::selector_cache::get_cached_selector;
+use crate::xpath::{self, get_cached_xpath};
+
+/// A parsed document shared between selector lists.
+///
+/// The lock is reentrant so that a thread can query a document from within a
+/// callback that already holds it.
+pub(crate) type SharedDocument = Arc<ReentrantMutex<Html>>;
+
+/// An ordered list of matches in an HTML document.
+///
+/// Cloning a list is cheap: the document is shared, not copied.
+#[derive(Clone)]
+pub struct SelectorList {
+    document: SharedDocument,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Item {
+    Node(NodeId),
+    Text(String),
+}
+
+/// The pseudo-element a CSS query ends with.
+enum Pseudo<'a> {
+    None,
+    Text,
+    Attr(&'a str),
+}
+
+impl SelectorList {
+    /// Creates a list holding the whole document.
+    pub fn from_html(html: Html) -> Self {
+        Self::from_document(Arc::new(ReentrantMutex::new(html)))
+    }
+
+    pub(crate) fn from_document(document: SharedDocument) -> Self {
+        let root = document.lock().tree.root().id();
+        Self {
+            document,
+            items: vec![Item::Node(root)],
+        }
+    }
+
+    pub(crate) fn from_items(document: SharedDocument, items: Vec<Item>) -> Self {
+        Self { document, items }
+    }
+
+    fn with_items(&self, items: Vec<Item>) -> Self {
+        Self {
+            document: Arc::clone(&self.document),
+            items,
+        }
+    }
+
+    /// Runs a CSS query below each element of the list.
+    ///
+    /// The query may end with `::text` to select the text nodes of the
+    /// matches, or `::attr(name)` to select the value of an attribute.
+    /// Returns [`SpiderError::HtmlParseError`] if the selector is invalid.
+    pub fn css(&self, query: &str) -> Result<SelectorList, SpiderError> {
+        let (query, pseudo) = split_pseudo(query.trim());
+
+        let matches = if query.is_empty() {
+            self.clone()
+        } else {
+            let selector = get_cached_selector(query).ok_or_else(|| {
+                SpiderError::HtmlParseError(format!("Invalid CSS selector: {query}"))
+            })?;
+
+            let html = self.document.lock();
+            let mut seen = HashSet::new();
+            let mut items = Vec::new();
+            for id in self.node_ids() {
+                let Some(node) = html.tree.get(id) else {
+                    continue;
+                };
+                let found: Vec<NodeId> = match ElementRef::wrap(node) {
+                    Some(element) => element.select(&selector).map(|e| e.id()).collect(),
+                    None if node.parent().is_none() => {
+                        html.select(&selector).map(|e| e.id()).collect()
+                    }
+                    None => Vec::new(),
+                };
+                items.extend(
+                    found
+                        .into_iter()
+                        .filter(|id| seen.insert(*id))
+                        .map(Item::Node),
+                );
+            }
+            drop(html);
+            self.with_items(items)
+        };
+
+        Ok(match pseudo {
+            Pseudo::None => matches,
+            Pseudo::Text => matches.text(),
+            Pseudo::Attr(name) => matches.attr(name),
+        })
+    }
+
+    /// Evaluates an XPath 1.0 expression with each element of the list as the
+    /// context node.
+    ///
+    /// Selected nodes are merged in document order. Attributes, and string,
+    /// number or boolean results, are returned as text. Returns
+    /// [`SpiderError::XPathError`] if the expression is invalid.
+    pub fn xpath(&self, expr: &str) -> Result<SelectorList, SpiderError> {
+        let xpath = get_cached_xpath(expr)?;
+        let contexts: Vec<NodeId> = self.node_ids().collect();
+        let items = xpath::select(&self.document.lock(), &xpath, &contexts)?;
+        Ok(self.with_items(items))
+    }
+
+    /// Selects the text nodes directly inside each element, like `::text`.
+    pub fn text(&self) -> SelectorList {
+        let html = self.document.lock();
+        let items = self
+            .node_ids()
+            .filter_map(|id| html.tree.get(id))
+            .flat_map(|node| node.children())
+            .filter_map(|child| match child.value() {
+                Node::Text(text) => Some(Item::Text(text.to_string())),
+                _ => None,
+            })
+            .collect();
+        drop(html);
+        self.with_items(items)
+    }
+
+    /// Selects the value of an attribute of each element that has it, like
+    /// `::attr(name)`.
+    pub fn attr(&self, name: &str) -> SelectorList {
+        let html = self.document.lock();
+        let items = self
+            .node_ids()
+            .filter_map(|id| html.tree.get(id))
+            .filter_map(ElementRef::wrap)
+            .filter_map(|element| element.value().attr(name))
+            .map(|value| Item::Text(value.to_owned()))
+            .collect();
+        drop(html);
+        self.with_items(items)
+    }
+
+    /// Returns the first match as a string, see [`SelectorList::get_all`].
+    pub fn get(&self) -> Option<String> {
+        self.items.first().map(|item| self.serialize(item))
+    }
+
+    /// Returns every match as a string: the outer HTML of elements and
+    /// comments, and the value of text nodes and attributes.
+    pub fn get_all(&self) -> Vec<String> {
+        self.items.iter().map(|item| self.serialize(item)).collect()
+    }
+
+    /// Returns the text of the first match, with its whitespace collapsed.
+    ///
+    /// Unlike `::text`, this includes the text of nested elements.
+    pub fn text_content(&self) -> Option<String> {
+        let item = self.items.first()?;
+        Some(match item {
+            Item::Text(text) => text.split_whitespace().collect::<Vec<_>>().join(" "),
+            Item::Node(id) => {
+                let html = self.document.lock();
+                let node = html.tree.get(*id)?;
+                node.descendants()
+                    .filter_map(|node| match node.value() {
+                        Node::Text(text) => Some(&**text),
+                        _ => None,
+                    })
+                    .flat_map(str::split_whitespace)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+        })
+    }
+
+    /// Applies a regular expression to every match, see
+    /// [`SelectorList::get_all`].
+    ///
+    /// Returns the first capture group of each match of the expression, or
+    /// the whole match if it has no groups.
+    pub fn re(&self, pattern: &str) -> Result<Vec<String>, SpiderError> {
+        let regex = compile_regex(pattern)?;
+        Ok(self
+            .get_all()
+            .iter()
+            .flat_map(|text| regex_matches(&regex, text))
+            .collect())
+    }
+
+    /// Returns the first result of [`SelectorList::re`].
+    pub fn re_first(&self, pattern: &str) -> Result<Option<String>, SpiderError> {
+        let regex = compile_regex(pattern)?;
+        Ok(self
+            .get_all()
+            .iter()
+            .find_map(|text| regex_matches(&regex, text).into_iter().next()))
+    }
+
+    /// Returns the number of matches.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if nothing matched.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the first match as a list of its own.
+    pub fn first(&self) -> Option<SelectorList> {
+        self.items
+            .first()
+            .map(|item| self.with_items(vec![item.clone()]))
+    }
+
+    /// Iterates over the matches, each as a list of its own.
+    pub fn iter(&self) -> impl Iterator<Item = SelectorList> + '_ {
+        self.items
+            .iter()
+            .map(|item| self.with_items(vec![item.clone()]))
+    }
+
+    fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.items.iter().filter_map(|item| match item {
+            Item::Node(id) => Some(*id),
+            Item::Text(_) => None,
+        })
+    }
+
+    fn serialize(&self, item: &Item) -> String {
+        match item {
+            Item::Text(text) => text.clone(),
+            Item::Node(id) => {
+                let html = self.document.lock();
+                match html.tree.get(*id) {
+                    Some(node) => match node.value() {
+                        Node::Element(_) => ElementRef::wrap(node)
+                            .map(|element| element.html())
+                            .unwrap_or_default(),
+                        Node::Text(text) => text.to_string(),
+                        Node::Comment(comment) => format!("<!--{}-->", &**comment),
+                        Node::ProcessingInstruction(pi) => {
+                            format!("<?{} {}?>", &*pi.target, &*pi.data)
+                        }
+                        _ if node.parent().is_none() => html.html(),
+                        _ => String::new(),
+                    },
+                    None => String::new(),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SelectorList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.get_all()).finish()
+    }
+}
+
+/// Splits a trailing `::text` or `::attr(name)` from a CSS query.
+fn split_pseudo(query: &str) -> (&str, Pseudo<'_>) {
+    if let Some(query) = query.strip_suffix("::text") {
+        return (query.trim_end(), Pseudo::Text);
+    }
+    if let Some(rest) = query.strip_suffix(')')
+        && let Some(start) = rest.rfind("::attr(")
+    {
+        let name = rest[start + "::attr(".len()..].trim();
+        let name = name.trim_matches(|c| c == '"' || c == '\'');
+        return (query[..start].trim_end(), Pseudo::Attr(name));
+    }
+    (query, Pseudo::None)
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex, SpiderError> {
+    Regex::new(pattern)
+        .map_err(|e| SpiderError::ConfigurationError(format!("Invalid regex '{}': {}", pattern, e)))
+}
+
+fn regex_matches(regex: &Regex, text: &str) -> Vec<String> {
+    regex
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let group = if captures.len() > 1 {
+                captures.iter().skip(1).flatten().next()
+            } else {
+                captures.get(0)
+            };
+            group.map(|m| m.as_str().to_owned())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><body>
+        <div class="product" data-id="1">
+          <h2>Widget <small>new</small></h2>
+          <a href="/widget">Details</a>
+          <span class="price">Price: $12.50</span>
+        </div>
+        <div class="product" data-id="2">
+          <h2>Gadget</h2>
+          <a href="/gadget" title='Gadget page'>Details</a>
+          <span class="price">Price: $7</span>
+        </div>
+        <!-- end -->
+    </body></html>"#;
+
+    fn page() -> SelectorList {
+        SelectorList::from_html(Html::parse_document(PAGE))
+    }
+
+    #[test]
+    fn text_and_attr_pseudo_elements() {
+        let page = page();
+        assert_eq!(
+            page.css("h2::text").unwrap().get_all(),
+            ["Widget ", "Gadget"]
+        );
+        assert_eq!(
+            page.css("a::attr(href)").unwrap().get_all(),
+            ["/widget", "/gadget"]
+        );
+        assert_eq!(
+            page.css("a::attr('title')").unwrap().get_all(),
+            ["Gadget page"]
+        );
+        assert_eq!(
+            page.css("div.product").unwrap().attr("data-id").get_all(),
+            ["1", "2"]
+        );
+        assert_eq!(
+            page.css("h2").unwrap().text().get_all(),
+            ["Widget ", "Gadget"]
+        );
+        assert_eq!(
+            page.css("h2").unwrap().text_content().as_deref(),
+            Some("Widget new")
+        );
+        assert!(page.css("a::attr(missing)").unwrap().is_empty());
+    }
+
+    #[test]
+    fn get_and_get_all() {
+        let page = page();
+        let links = page.css("a").unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(
+            links.get().as_deref(),
+            Some(r#"<a href="/widget">Details</a>"#)
+        );
+        assert_eq!(links.get_all().len(), 2);
+        assert_eq!(page.css("table").unwrap().get(), None);
+        assert!(page.css("div[").is_err());
+
+        let comment = page.xpath("//comment()").unwrap();
+        assert_eq!(comment.get().as_deref(), Some("<!-- end -->"));
+    }
+
+    #[test]
+    fn chained_queries_stay_below_their_matches() {
+        let page = page();
+        let products = page.css("div.product").unwrap();
+        let names: Vec<Option<String>> = products
+            .iter()
+            .map(|product| product.css("h2::text").unwrap().get())
+            .collect();
+        assert_eq!(names, [Some("Widget ".into()), Some("Gadget".into())]);
+
+        // Matches found from several elements are merged without duplicates.
+        assert_eq!(page.css("body, div").unwrap().css("a").unwrap().len(), 2);
+
+        let second = products.iter().nth(1).unwrap();
+        assert_eq!(second.xpath("./a/@href").unwrap().get_all(), ["/gadget"]);
+        assert_eq!(
+            second
+                .xpath("./span")
+                .unwrap()
+                .css("::text")
+                .unwrap()
+                .get_all(),
+            ["Price: $7"]
+        );
+        assert_eq!(
+            page.xpath("//div")
+                .unwrap()
+                .css("a::attr(href)")
+                .unwrap()
+                .get_all(),
+            ["/widget", "/gadget"]
+        );
+        assert_eq!(products.xpath("count(.//a)").unwrap().get_all(), ["1", "1"]);
+        assert!(products.xpath("//[").is_err());
+    }
+
+    #[test]
+    fn regular_expressions() {
+        let prices = page().css(".price::text").unwrap();
+        assert_eq!(prices.re(r"\$([\d.]+)").unwrap(), ["12.50", "7"]);
+        assert_eq!(prices.re(r"\d+").unwrap(), ["12", "50", "7"]);
+        assert_eq!(
+            prices.re_first(r"\$([\d.]+)").unwrap().as_deref(),
+            Some("12.50")
+        );
+        assert_eq!(prices.re_first(r"€").unwrap(), None);
+        assert!(prices.re("(").is_err());
+    }
+
+    #[test]
+    fn nested_selections_reenter_the_document_lock() {
+        let page = page();
+        let products = page.css("div.product").unwrap();
+
+        let guard = page.document.lock();
+        let prices: Vec<String> = products
+            .iter()
+            .flat_map(|product| {
+                let link = product.css("a").unwrap();
+                // Still holding the lock, as a callback given the document
+                // would.
+                link.xpath("..")
+                    .unwrap()
+                    .css(".price::text")
+                    .unwrap()
+                    .get_all()
+            })
+            .collect();
+        drop(guard);
+
+        assert_eq!(prices, ["Price: $12.50", "Price: $7"]);
+
+        // A response's selectors share the document its callbacks lock.
+        let response = crate::response::Response::new(
+            url::Url::parse("https://example.com/").unwrap(),
+            reqwest::StatusCode::OK,
+            http::HeaderMap::new(),
+            bytes::Bytes::from_static(PAGE.as_bytes()),
+        );
+        let links = response.with_html(|_| response.css("a::attr(href)").unwrap().get_all());
+        assert_eq!(links, ["/widget", "/gadget"]);
+    }
+}
@@ -0,0 +1,647 @@
+//! # Sitemap Module
+//!
+//! Parses XML sitemaps, sitemap indexes and plain-text sitemaps into requests.
+//!
+//! ## Overview
+//!
+//! `SitemapParser` reads a sitemap body as a stream of `SitemapEntry` values,
+//! without building a document tree, so sitemaps with tens of thousands of
+//! URLs are parsed in constant memory. Gzipped bodies are recognised by their
+//! magic bytes and decompressed on the fly. Each entry is either a page URL,
+//! with its `lastmod`, `changefreq`, `priority` and `hreflang` alternates, or a
+//! nested sitemap listed by a sitemap index.
+//!
+//! Requests built from entries carry those values in their metadata under the
+//! typed keys of this module.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use spider_util::sitemap::{self, SitemapEntry};
+//!
+//! for entry in response.sitemap() {
+//!     match entry? {
+//!         SitemapEntry::Sitemap(nested) => queue.push(nested.to_request()),
+//!         SitemapEntry::Url(url) => {
+//!             let request = url.to_request();
+//!             let lastmod = request.get_typed_meta(&sitemap::LASTMOD);
+//!             queue.push(request);
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::io::{BufRead, BufReader, Read};
+
+use flate2::read::GzDecoder;
+use quick_xml::Reader;
+use quick_xml::XmlVersion;
+use quick_xml::events::{BytesStart, Event};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::SpiderError;
+use crate::meta::{self, MetaKey};
+use crate::request::Request;
+use crate::response::Response;
+use crate::utils::resolve_xml_reference;
+
+/// The largest uncompressed sitemap that is read; the rest is ignored.
+pub const MAX_SITEMAP_SIZE: u64 = 50 * 1024 * 1024;
+
+/// The `<lastmod>` date of a sitemap entry, as written.
+pub const LASTMOD: MetaKey<String> = MetaKey::new("sitemap_lastmod");
+
+/// The `<changefreq>` of a sitemap entry.
+pub const CHANGEFREQ: MetaKey<String> = MetaKey::new("sitemap_changefreq");
+
+/// The `<priority>` of a sitemap entry, between 0.0 and 1.0.
+pub const PRIORITY: MetaKey<f64> = MetaKey::new("sitemap_priority");
+
+/// The alternate language versions of a sitemap entry.
+pub const ALTERNATES: MetaKey<Vec<Alternate>> = MetaKey::new("sitemap_alternates");
+
+/// An alternate language version of a page (`<xhtml:link rel="alternate">`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alternate {
+    /// The language of the version, such as `en-GB` or `x-default`.
+    pub hreflang: String,
+    /// The URL of the version.
+    pub url: Url,
+}
+
+/// A page listed in a sitemap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapUrl {
+    /// The URL of the page (`<loc>`).
+    pub loc: Url,
+    /// The date the page was last modified (`<lastmod>`), as written.
+    pub lastmod: Option<String>,
+    /// How often the page is expected to change (`<changefreq>`).
+    pub changefreq: Option<String>,
+    /// The priority of the page relative to the rest of the site (`<priority>`).
+    pub priority: Option<f64>,
+    /// The alternate language versions of the page.
+    pub alternates: Vec<Alternate>,
+}
+
+impl SitemapUrl {
+    /// Creates a request for the page, carrying the entry's values in its metadata.
+    pub fn to_request(&self) -> Request {
+        let request = Request::new(self.loc.clone());
+        if let Some(lastmod) = &self.lastmod {
+            let _ = meta::set(&request.meta, LASTMOD.name().into(), lastmod);
+        }
+        if let Some(changefreq) = &self.changefreq {
+            let _ = meta::set(&request.meta, CHANGEFREQ.name().into(), changefreq);
+        }
+        if let Some(priority) = self.priority {
+            let _ = meta::set(&request.meta, PRIORITY.name().into(), priority);
+        }
+        if !self.alternates.is_empty() {
+            let _ = meta::set(&request.meta, ALTERNATES.name().into(), &self.alternates);
+        }
+        request
+    }
+}
+
+/// A nested sitemap listed in a sitemap index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapRef {
+    /// The URL of the nested sitemap (`<loc>`).
+    pub loc: Url,
+    /// The date the nested sitemap was last modified (`<lastmod>`), as written.
+    pub lastmod: Option<String>,
+}
+
+impl SitemapRef {
+    /// Creates a request for the nested sitemap.
+    pub fn to_request(&self) -> Request {
+        let request = Request::new(self.loc.clone());
+        if let Some(lastmod) = &self.lastmod {
+            let _ = meta::set(&request.meta, LASTMOD.name().into(), lastmod);
+        }
+        request
+    }
+}
+
+/// An entry of a sitemap or sitemap index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SitemapEntry {
+    /// A page, from a `<url>` element.
+    Url(SitemapUrl),
+    /// A nested sitemap, from a `<sitemap>` element of a sitemap index.
+    Sitemap(SitemapRef),
+}
+
+impl SitemapEntry {
+    /// Returns the URL of the entry.
+    pub fn loc(&self) -> &Url {
+        match self {
+            SitemapEntry::Url(url) => &url.loc,
+            SitemapEntry::Sitemap(sitemap) => &sitemap.loc,
+        }
+    }
+
+    /// Returns `true` if the entry is a nested sitemap.
+    pub fn is_sitemap(&self) -> bool {
+        matches!(self, SitemapEntry::Sitemap(_))
+    }
+
+    /// Creates a request for the entry.
+    pub fn to_request(&self) -> Request {
+        match self {
+            SitemapEntry::Url(url) => url.to_request(),
+            SitemapEntry::Sitemap(sitemap) => sitemap.to_request(),
+        }
+    }
+}
+
+/// A streaming parser over the entries of a sitemap.
+///
+/// Entries with a missing or invalid `<loc>` are skipped. Iteration stops
+/// after the first error.
+pub struct SitemapParser<'a> {
+    base: Url,
+    format: Format<'a>,
+}
+
+enum Format<'a> {
+    Xml(Box<XmlState<'a>>),
+    Text(Box<dyn BufRead + 'a>),
+    Done,
+}
+
+struct XmlState<'a> {
+    reader: Reader<Box<dyn BufRead + 'a>>,
+    buf: Vec<u8>,
+    path: Vec<String>,
+    entry: Option<Pending>,
+    field: Option<Field>,
+    text: String,
+}
+
+#[derive(Default)]
+struct Pending {
+    is_sitemap: bool,
+    loc: Option<String>,
+    lastmod: Option<String>,
+    changefreq: Option<String>,
+    priority: Option<String>,
+    alternates: Vec<(String, String)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Loc,
+    Lastmod,
+    Changefreq,
+    Priority,
+}
+
+impl<'a> SitemapParser<'a> {
+    /// Creates a parser over a sitemap body. Relative locations are resolved
+    /// against `base`.
+    pub fn new(body: &'a [u8], base: Url) -> Self {
+        Self::with_limit(body, base, MAX_SITEMAP_SIZE)
+    }
+
+    /// Creates a parser that reads at most `limit` uncompressed bytes.
+    fn with_limit(body: &'a [u8], base: Url, limit: u64) -> Self {
+        let reader: Box<dyn BufRead + 'a> = if body.starts_with(&[0x1f, 0x8b]) {
+            Box::new(BufReader::new(GzDecoder::new(body).take(limit)))
+        } else {
+            Box::new(body.take(limit))
+        };
+        Self::from_reader(reader, base)
+    }
+
+    /// Creates a parser over the body of a sitemap response.
+    pub fn from_response(response: &'a Response) -> Self {
+        Self::new(&response.body, response.url.clone())
+    }
+
+    fn from_reader(mut reader: Box<dyn BufRead + 'a>, base: Url) -> Self {
+        let is_xml = match reader.fill_buf() {
+            Ok(head) => {
+                let head = head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head);
+                head.iter()
+                    .find(|b| !b.is_ascii_whitespace())
+                    .is_none_or(|&b| b == b'<')
+            }
+            Err(_) => true,
+        };
+
+        let format = if is_xml {
+            Format::Xml(Box::new(XmlState {
+                reader: Reader::from_reader(reader),
+                buf: Vec::new(),
+                path: Vec::new(),
+                entry: None,
+                field: None,
+                text: String::new(),
+            }))
+        } else {
+            Format::Text(reader)
+        };
+
+        Self { base, format }
+    }
+
+    /// Turns every entry into a request, see [`SitemapEntry::to_request`].
+    pub fn requests(self) -> impl Iterator<Item = Result<Request, SpiderError>> + 'a {
+        self.map(|entry| entry.map(|entry| entry.to_request()))
+    }
+
+    fn next_text(reader: &mut Box<dyn BufRead + 'a>) -> Option<Result<SitemapEntry, SpiderError>> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&line);
+                    let line = line.trim_matches(|c: char| c.is_whitespace() || c == '\u{feff}');
+                    // Plain-text sitemaps list one absolute URL per line.
+                    if let Ok(loc) = Url::parse(line)
+                        && matches!(loc.scheme(), "http" | "https")
+                    {
+                        return Some(Ok(SitemapEntry::Url(SitemapUrl {
+                            loc,
+                            lastmod: None,
+                            changefreq: None,
+                            priority: None,
+                            alternates: Vec::new(),
+                        })));
+                    }
+                }
+                Err(err) => return Some(Err(err.into())),
+            }
+        }
+    }
+
+    fn next_xml(state: &mut XmlState<'a>, base: &Url) -> Option<Result<SitemapEntry, SpiderError>> {
+        let XmlState {
+            reader,
+            buf,
+            path,
+            entry,
+            field,
+            text,
+        } = state;
+
+        loop {
+            buf.clear();
+            let event = match reader.read_event_into(buf) {
+                Ok(event) => event,
+                Err(err) => return Some(Err(err.into())),
+            };
+
+            match event {
+                Event::Start(start) => {
+                    let name = start.local_name().as_ref().to_owned();
+                    let parent = path.last().map(String::as_str);
+                    match (parent, name.as_str()) {
+                        (Some("urlset"), "url") => *entry = Some(Pending::default()),
+                        (Some("sitemapindex"), "sitemap") => {
+                            *entry = Some(Pending {
+                                is_sitemap: true,
+                                ..Pending::default()
+                            })
+                        }
+                        (Some("url" | "sitemap"), _) if entry.is_some() => {
+                            *field = match name.as_str() {
+                                "loc" => Some(Field::Loc),
+                                "lastmod" => Some(Field::Lastmod),
+                                "changefreq" => Some(Field::Changefreq),
+                                "priority" => Some(Field::Priority),
+                                _ => None,
+                            };
+                            text.clear();
+                            if name == "link" {
+                                push_alternate(entry, &start);
+                            }
+                        }
+                        _ => {}
+                    }
+                    path.push(name);
+                }
+                Event::Empty(start)
+                    if path.last().is_some_and(|p| p == "url")
+                        && start.local_name().as_ref() == "link" =>
+                {
+                    push_alternate(entry, &start);
+                }
+                Event::Text(content) if field.is_some() => text.push_str(&content.xml10_content()),
+                Event::CData(content) if field.is_some() => text.push_str(&content.xml10_content()),
+                Event::GeneralRef(reference) if field.is_some() => {
+                    text.push_str(&resolve_xml_reference(&reference))
+                }
+                Event::End(_) => {
+                    let name = path.pop();
+                    if let Some(current) = field.take() {
+                        let value = Some(text.trim().to_owned()).filter(|v| !v.is_empty());
+                        if let Some(pending) = entry.as_mut() {
+                            match current {
+                                Field::Loc => pending.loc = value,
+                                Field::Lastmod => pending.lastmod = value,
+                                Field::Changefreq => pending.changefreq = value,
+                                Field::Priority => pending.priority = value,
+                            }
+                        }
+                    } else if matches!(name.as_deref(), Some("url" | "sitemap"))
+                        && let Some(pending) = entry.take()
+                        && let Some(parsed) = pending.into_entry(base)
+                    {
+                        return Some(Ok(parsed));
+                    }
+                }
+                Event::Eof => return None,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Iterator for SitemapParser<'_> {
+    type Item = Result<SitemapEntry, SpiderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = match &mut self.format {
+            Format::Xml(state) => Self::next_xml(state, &self.base),
+            Format::Text(reader) => Self::next_text(reader),
+            Format::Done => None,
+        };
+        if !matches!(next, Some(Ok(_))) {
+            self.format = Format::Done;
+        }
+        next
+    }
+}
+
+impl Pending {
+    fn into_entry(self, base: &Url) -> Option<SitemapEntry> {
+        let loc = resolve(base, self.loc.as_deref()?)?;
+        if self.is_sitemap {
+            return Some(SitemapEntry::Sitemap(SitemapRef {
+                loc,
+                lastmod: self.lastmod,
+            }));
+        }
+
+        let alternates = self
+            .alternates
+            .into_iter()
+            .filter_map(|(hreflang, href)| {
+                Some(Alternate {
+                    hreflang,
+                    url: resolve(base, &href)?,
+                })
+            })
+            .collect();
+        Some(SitemapEntry::Url(SitemapUrl {
+            loc,
+            lastmod: self.lastmod,
+            changefreq: self.changefreq,
+            priority: self
+                .priority
+                .and_then(|p| p.parse::<f64>().ok())
+                .filter(|p| (0.0..=1.0).contains(p)),
+            alternates,
+        }))
+    }
+}
+
+/// Records an `<xhtml:link rel="alternate" hreflang=".." href="..">` element.
+fn push_alternate(entry: &mut Option<Pending>, link: &BytesStart<'_>) {
+    let Some(pending) = entry.as_mut() else {
+        return;
+    };
+    let attribute = |name: &str| {
+        link.attributes()
+            .flatten()
+            .find(|attr| attr.key.local_name().as_ref() == name)
+            .and_then(|attr| attr.normalized_value(XmlVersion::Implicit1_0).ok())
+            .map(|value| value.trim().to_owned())
+    };
+    if attribute("rel").is_some_and(|rel| rel.eq_ignore_ascii_case("alternate"))
+        && let (Some(hreflang), Some(href)) = (attribute("hreflang"), attribute("href"))
+    {
+        pending.alternates.push((hreflang, href));
+    }
+}
+
+fn resolve(base: &Url, loc: &str) -> Option<Url> {
+    base.join(loc.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use super::*;
+
+    const URLSET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+        xmlns:xhtml="http://www.w3.org/1999/xhtml">
+  <url>
+    <loc>https://example.com/a?x=1&amp;y=2</loc>
+    <lastmod>2024-05-01</lastmod>
+    <changefreq>daily</changefreq>
+    <priority>0.8</priority>
+    <xhtml:link rel="alternate" hreflang="de" href="https://example.com/de/a"/>
+    <xhtml:link rel="alternate" hreflang="fr" href="/fr/a"/>
+    <xhtml:link rel="canonical" hreflang="en" href="https://example.com/en/a"/>
+  </url>
+  <url><loc><![CDATA[/relative]]></loc><priority>1.5</priority></url>
+  <url><lastmod>2024-05-02</lastmod></url>
+  <url><loc>ftp://example.com/file</loc></url>
+</urlset>"#;
+
+    fn base() -> Url {
+        Url::parse("https://example.com/sitemap.xml").unwrap()
+    }
+
+    fn entries(body: &[u8]) -> Vec<SitemapEntry> {
+        SitemapParser::new(body, base())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn locs(entries: &[SitemapEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.loc().as_str()).collect()
+    }
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn urlset_entries() {
+        let entries = entries(URLSET.as_bytes());
+        assert_eq!(
+            locs(&entries),
+            [
+                "https://example.com/a?x=1&y=2",
+                "https://example.com/relative"
+            ]
+        );
+
+        let SitemapEntry::Url(first) = &entries[0] else {
+            panic!("expected a page, got {:?}", entries[0]);
+        };
+        assert_eq!(first.lastmod.as_deref(), Some("2024-05-01"));
+        assert_eq!(first.changefreq.as_deref(), Some("daily"));
+        assert_eq!(first.priority, Some(0.8));
+        let alternates: Vec<(&str, &str)> = first
+            .alternates
+            .iter()
+            .map(|alternate| (alternate.hreflang.as_str(), alternate.url.as_str()))
+            .collect();
+        assert_eq!(
+            alternates,
+            [
+                ("de", "https://example.com/de/a"),
+                ("fr", "https://example.com/fr/a")
+            ]
+        );
+
+        // Priorities outside of 0.0..=1.0 are dropped.
+        let SitemapEntry::Url(second) = &entries[1] else {
+            panic!("expected a page, got {:?}", entries[1]);
+        };
+        assert_eq!(second.priority, None);
+        assert!(!entries[1].is_sitemap());
+    }
+
+    #[test]
+    fn sitemap_index_entries() {
+        let entries = entries(
+            br#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sitemap><loc>https://example.com/posts.xml</loc><lastmod>2024-01-01</lastmod></sitemap>
+                <sitemap><loc>pages.xml.gz</loc></sitemap>
+                <url><loc>https://example.com/ignored</loc></url>
+            </sitemapindex>"#,
+        );
+        assert_eq!(
+            entries,
+            [
+                SitemapEntry::Sitemap(SitemapRef {
+                    loc: Url::parse("https://example.com/posts.xml").unwrap(),
+                    lastmod: Some("2024-01-01".to_owned()),
+                }),
+                SitemapEntry::Sitemap(SitemapRef {
+                    loc: Url::parse("https://example.com/pages.xml.gz").unwrap(),
+                    lastmod: None,
+                }),
+            ]
+        );
+        assert!(entries.iter().all(SitemapEntry::is_sitemap));
+    }
+
+    #[test]
+    fn gzipped_sitemaps_are_detected_by_their_magic_bytes() {
+        let compressed = gzip(URLSET.as_bytes());
+        assert_eq!(entries(&compressed), entries(URLSET.as_bytes()));
+
+        let text = gzip(b"https://example.com/a\nhttps://example.com/b\n");
+        assert_eq!(
+            locs(&entries(&text)),
+            ["https://example.com/a", "https://example.com/b"]
+        );
+    }
+
+    #[test]
+    fn plain_text_sitemaps() {
+        let entries = entries(
+            "\u{feff}https://example.com/a\r\n\n  https://example.com/b  \nnot a url\n/relative\nftp://example.com/c\nhttps://example.com/c"
+                .as_bytes(),
+        );
+        assert_eq!(
+            locs(&entries),
+            [
+                "https://example.com/a",
+                "https://example.com/b",
+                "https://example.com/c"
+            ]
+        );
+    }
+
+    #[test]
+    fn reading_stops_at_the_size_limit() {
+        let mut body = String::from("<urlset>");
+        for i in 0..100 {
+            body.push_str(&format!("<url><loc>https://example.com/{i}</loc></url>"));
+        }
+        body.push_str("</urlset>");
+        let limit = body.find("/50<").unwrap() as u64;
+
+        let count = |body: &[u8]| {
+            SitemapParser::with_limit(body, base(), limit)
+                .take_while(Result::is_ok)
+                .count()
+        };
+        assert_eq!(count(body.as_bytes()), 50);
+        assert_eq!(count(&gzip(body.as_bytes())), 50);
+
+        let lines: String = (0..100)
+            .map(|i| format!("https://example.com/{i}\n"))
+            .collect();
+        let limit = lines.find("https://example.com/50\n").unwrap() as u64;
+        let parsed = SitemapParser::with_limit(lines.as_bytes(), base(), limit).count();
+        assert_eq!(parsed, 50);
+    }
+
+    #[test]
+    fn malformed_xml_ends_with_an_error() {
+        let mut parser = SitemapParser::new(
+            b"<urlset><url><loc>https://example.com/a</loc></url><url></loc></urlset>",
+            base(),
+        );
+        assert!(matches!(parser.next(), Some(Ok(_))));
+        assert!(matches!(parser.next(), Some(Err(_))));
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn requests_carry_the_entry_values() {
+        let mut requests = SitemapParser::new(URLSET.as_bytes(), base()).requests();
+
+        let request = requests.next().unwrap().unwrap();
+        assert_eq!(request.url.as_str(), "https://example.com/a?x=1&y=2");
+        assert_eq!(
+            request.get_typed_meta(&LASTMOD).as_deref(),
+            Some("2024-05-01")
+        );
+        assert_eq!(
+            request.get_typed_meta(&CHANGEFREQ).as_deref(),
+            Some("daily")
+        );
+        assert_eq!(request.get_typed_meta(&PRIORITY), Some(0.8));
+        let alternates = request.get_typed_meta(&ALTERNATES).unwrap();
+        assert_eq!(alternates.len(), 2);
+        assert_eq!(alternates[0].hreflang, "de");
+
+        let request = requests.next().unwrap().unwrap();
+        assert!(request.meta.is_empty());
+
+        let nested = SitemapEntry::Sitemap(SitemapRef {
+            loc: Url::parse("https://example.com/posts.xml").unwrap(),
+            lastmod: Some("2024-01-01".to_owned()),
+        })
+        .to_request();
+        assert_eq!(nested.url.as_str(), "https://example.com/posts.xml");
+        assert_eq!(
+            nested.get_typed_meta(&LASTMOD).as_deref(),
+            Some("2024-01-01")
+        );
+        assert_eq!(nested.meta.len(), 1);
+    }
+}
@@ -0,0 +1,485 @@
+//! # Structured Data Module
+//!
+//! Extracts the machine-readable metadata embedded in HTML pages.
+//!
+//! ## Overview
+//!
+//! `StructuredData` gathers, as `serde_json::Value`s:
+//! - **JSON-LD** blocks from `<script type="application/ld+json">`, with
+//!   `@graph` containers flattened into their nodes. Slightly invalid JSON is
+//!   read with [`lenient_json`](crate::lenient_json).
+//! - **Microdata** items (`itemscope`/`itemprop`), in the JSON shape of the
+//!   W3C Microdata specification: `{"type": [..], "id": .., "properties": {..}}`.
+//! - **OpenGraph** and **Twitter card** `<meta>` properties, keyed by property
+//!   name; repeated properties become arrays.
+//! - **RDFa Lite** items (`vocab`/`typeof`/`property`), as JSON-LD style
+//!   objects.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let data = response.structured_data();
+//! for node in &data.json_ld {
+//!     if node["@type"] == "Product" {
+//!         println!("{}", node["name"]);
+//!     }
+//! }
+//! let title = data.opengraph.get("og:title");
+//! ```
+
+use std::collections::HashSet;
+
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::lenient_json;
+use crate::selector_cache::get_cached_selector;
+
+/// The `<meta property>` prefixes collected as OpenGraph properties.
+const OPENGRAPH_PREFIXES: &[&str] = &[
+    "og:", "fb:", "article:", "book:", "profile:", "music:", "video:", "product:",
+];
+
+/// The structured data found in a document.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StructuredData {
+    /// The JSON-LD nodes, with `@graph` containers flattened.
+    pub json_ld: Vec<Value>,
+    /// The top-level Microdata items.
+    pub microdata: Vec<Value>,
+    /// The OpenGraph properties, such as `og:title`, keyed by name.
+    pub opengraph: Map<String, Value>,
+    /// The Twitter card properties, such as `twitter:card`, keyed by name.
+    pub twitter: Map<String, Value>,
+    /// The top-level RDFa Lite items.
+    pub rdfa: Vec<Value>,
+}
+
+impl StructuredData {
+    /// Extracts the structured data of a document served from `base`.
+    pub fn extract(html: &Html, base: &Url) -> Self {
+        Self {
+            json_ld: json_ld(html),
+            microdata: microdata(html, base),
+            opengraph: meta_properties(html, "property", |name| {
+                OPENGRAPH_PREFIXES
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+            }),
+            twitter: meta_properties(html, "name", |name| name.starts_with("twitter:")),
+            rdfa: rdfa(html, base),
+        }
+    }
+
+    /// Returns `true` if no structured data was found.
+    pub fn is_empty(&self) -> bool {
+        self.json_ld.is_empty()
+            && self.microdata.is_empty()
+            && self.opengraph.is_empty()
+            && self.twitter.is_empty()
+            && self.rdfa.is_empty()
+    }
+
+    /// Returns all of the structured data as one JSON object.
+    pub fn to_json_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+/// Collects the JSON-LD nodes of a document, flattening `@graph` containers.
+fn json_ld(html: &Html) -> Vec<Value> {
+    let Some(selector) = get_cached_selector("script[type]") else {
+        return Vec::new();
+    };
+
+    let mut nodes = Vec::new();
+    for script in html.select(&selector) {
+        let media_type = script.value().attr("type").unwrap_or_default();
+        let media_type = media_type.split(';').next().unwrap_or_default().trim();
+        if !media_type.eq_ignore_ascii_case("application/ld+json") {
+            continue;
+        }
+
+        let text: String = script.text().collect();
+        let text = text.trim();
+        let text = text.strip_prefix("<!--").unwrap_or(text);
+        let text = text.strip_suffix("-->").unwrap_or(text);
+        let text = text.trim();
+        let text = text.strip_prefix("//<![CDATA[").unwrap_or(text);
+        let text = text.strip_suffix("//]]>").unwrap_or(text);
+
+        if let Ok(value) = lenient_json::from_str(text) {
+            flatten_json_ld(value, None, &mut nodes);
+        }
+    }
+    nodes
+}
+
+fn flatten_json_ld(value: Value, context: Option<&Value>, nodes: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                flatten_json_ld(item, context, nodes);
+            }
+        }
+        Value::Object(mut object) => {
+            if let Some(graph) = object.remove("@graph") {
+                // Graph nodes inherit the container's context.
+                let context = object.get("@context").or(context).cloned();
+                flatten_json_ld(graph, context.as_ref(), nodes);
+                if object.keys().any(|key| key != "@context") {
+                    nodes.push(Value::Object(object));
+                }
+            } else {
+                if let Some(context) = context
+                    && !object.contains_key("@context")
+                {
+                    object.insert("@context".to_owned(), context.clone());
+                }
+                nodes.push(Value::Object(object));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collects the `<meta>` properties whose `attribute` name passes `accept`.
+fn meta_properties(
+    html: &Html,
+    attribute: &str,
+    accept: impl Fn(&str) -> bool,
+) -> Map<String, Value> {
+    let mut properties = Map::new();
+    let Some(selector) = get_cached_selector("meta[content]") else {
+        return properties;
+    };
+
+    for element in html.select(&selector) {
+        // Twitter cards are also commonly written with `property`.
+        let name = element
+            .value()
+            .attr(attribute)
+            .or_else(|| element.value().attr("property"))
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if !accept(&name) {
+            continue;
+        }
+        let content = Value::String(
+            element
+                .value()
+                .attr("content")
+                .unwrap_or_default()
+                .to_owned(),
+        );
+        match properties.get_mut(&name) {
+            Some(Value::Array(values)) => values.push(content),
+            Some(existing) => *existing = Value::Array(vec![existing.take(), content]),
+            None => {
+                properties.insert(name, content);
+            }
+        }
+    }
+    properties
+}
+
+/// Collects the top-level Microdata items of a document.
+fn microdata(html: &Html, base: &Url) -> Vec<Value> {
+    let Some(selector) = get_cached_selector("[itemscope]") else {
+        return Vec::new();
+    };
+
+    html.select(&selector)
+        .filter(|element| element.value().attr("itemprop").is_none())
+        .map(|element| microdata_item(html, element, base, 0, &HashSet::new()))
+        .collect()
+}
+
+/// Converts a Microdata item. `expanded` holds the `itemref` targets already
+/// expanded by the enclosing items, which are not expanded again.
+fn microdata_item<'a>(
+    html: &'a Html,
+    item: ElementRef<'a>,
+    base: &Url,
+    depth: usize,
+    expanded: &HashSet<&'a str>,
+) -> Value {
+    let mut object = Map::new();
+    let attr = |name: &str| item.value().attr(name).map(str::trim);
+
+    if let Some(types) = attr("itemtype") {
+        let types: Vec<Value> = types.split_ascii_whitespace().map(Value::from).collect();
+        object.insert("type".to_owned(), Value::Array(types));
+    }
+    if let Some(id) = attr("itemid") {
+        let id = base.join(id).map_or_else(|_| id.to_owned(), String::from);
+        object.insert("id".to_owned(), Value::String(id));
+    }
+
+    // Each `itemref` target is expanded at most once per item, and never by
+    // an item it is already being expanded for.
+    let mut expanded = expanded.clone();
+    let refs: Vec<&str> = attr("itemref")
+        .into_iter()
+        .flat_map(str::split_ascii_whitespace)
+        .filter(|id| expanded.insert(id))
+        .collect();
+
+    let mut properties = Map::new();
+    collect_microdata_properties(html, item, base, depth, &expanded, &mut properties);
+    for id in refs {
+        if let Some(referenced) = element_by_id(html, id) {
+            add_microdata_property(html, referenced, base, depth, &expanded, &mut properties);
+            if referenced.value().attr("itemscope").is_none() {
+                collect_microdata_properties(
+                    html,
+                    referenced,
+                    base,
+                    depth,
+                    &expanded,
+                    &mut properties,
+                );
+            }
+        }
+    }
+    object.insert("properties".to_owned(), Value::Object(properties));
+    Value::Object(object)
+}
+
+/// Adds the properties below `element`, without entering nested items.
+fn collect_microdata_properties<'a>(
+    html: &'a Html,
+    element: ElementRef<'a>,
+    base: &Url,
+    depth: usize,
+    expanded: &HashSet<&'a str>,
+    properties: &mut Map<String, Value>,
+) {
+    for child in element.children().filter_map(ElementRef::wrap) {
+        add_microdata_property(html, child, base, depth, expanded, properties);
+        if child.value().attr("itemscope").is_none() {
+            collect_microdata_properties(html, child, base, depth, expanded, properties);
+        }
+    }
+}
+
+fn add_microdata_property<'a>(
+    html: &'a Html,
+    element: ElementRef<'a>,
+    base: &Url,
+    depth: usize,
+    expanded: &HashSet<&'a str>,
+    properties: &mut Map<String, Value>,
+) {
+    let Some(names) = element.value().attr("itemprop") else {
+        return;
+    };
+
+    let value = if element.value().attr("itemscope").is_some() {
+        // Guard against deeply nested items.
+        if depth >= 32 {
+            return;
+        }
+        microdata_item(html, element, base, depth + 1, expanded)
+    } else {
+        Value::String(microdata_value(element, base))
+    };
+
+    for name in names.split_ascii_whitespace() {
+        match properties.get_mut(name) {
+            Some(Value::Array(values)) => values.push(value.clone()),
+            _ => {
+                properties.insert(name.to_owned(), Value::Array(vec![value.clone()]));
+            }
+        }
+    }
+}
+
+/// The value of a Microdata property element, per the HTML specification.
+fn microdata_value(element: ElementRef, base: &Url) -> String {
+    let value = element.value();
+    let url_attr = match value.name() {
+        "meta" => return value.attr("content").unwrap_or_default().to_owned(),
+        "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => Some("src"),
+        "a" | "area" | "link" => Some("href"),
+        "object" => Some("data"),
+        "data" | "meter" => return value.attr("value").unwrap_or_default().to_owned(),
+        "time" => {
+            if let Some(datetime) = value.attr("datetime") {
+                return datetime.to_owned();
+            }
+            None
+        }
+        _ => None,
+    };
+
+    match url_attr {
+        Some(attr) => value
+            .attr(attr)
+            .and_then(|href| base.join(href.trim()).ok())
+            .map(String::from)
+            .unwrap_or_default(),
+        None => text_content(element),
+    }
+}
+
+/// Collects the top-level RDFa Lite items of a document.
+fn rdfa(html: &Html, base: &Url) -> Vec<Value> {
+    let Some(selector) = get_cached_selector("[typeof]") else {
+        return Vec::new();
+    };
+
+    html.select(&selector)
+        .filter(|element| {
+            element.value().attr("property").is_none()
+                || !element
+                    .ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .any(|ancestor| ancestor.value().attr("typeof").is_some())
+        })
+        .map(|element| rdfa_item(element, base))
+        .collect()
+}
+
+fn rdfa_item(item: ElementRef, base: &Url) -> Value {
+    let mut object = Map::new();
+
+    // The vocabulary is inherited from the closest ancestor declaring one.
+    let vocab = std::iter::once(item)
+        .chain(item.ancestors().filter_map(ElementRef::wrap))
+        .find_map(|element| element.value().attr("vocab"));
+    if let Some(vocab) = vocab {
+        object.insert(
+            "@context".to_owned(),
+            Value::String(vocab.trim().to_owned()),
+        );
+    }
+    if let Some(types) = item.value().attr("typeof") {
+        let mut types: Vec<Value> = types.split_ascii_whitespace().map(Value::from).collect();
+        let types = match types.len() {
+            0 => None,
+            1 => types.pop(),
+            _ => Some(Value::Array(types)),
+        };
+        if let Some(types) = types {
+            object.insert("@type".to_owned(), types);
+        }
+    }
+    if let Some(resource) = item.value().attr("resource") {
+        let id = base
+            .join(resource.trim())
+            .map_or_else(|_| resource.to_owned(), String::from);
+        object.insert("@id".to_owned(), Value::String(id));
+    }
+
+    collect_rdfa_properties(item, base, &mut object);
+    Value::Object(object)
+}
+
+fn collect_rdfa_properties(element: ElementRef, base: &Url, object: &mut Map<String, Value>) {
+    for child in element.children().filter_map(ElementRef::wrap) {
+        let is_item = child.value().attr("typeof").is_some();
+        if let Some(names) = child.value().attr("property") {
+            let value = if is_item {
+                rdfa_item(child, base)
+            } else {
+                Value::String(rdfa_value(child, base))
+            };
+            for name in names.split_ascii_whitespace() {
+                match object.get_mut(name) {
+                    Some(Value::Array(values)) => values.push(value.clone()),
+                    Some(existing) => {
+                        *existing = Value::Array(vec![existing.take(), value.clone()])
+                    }
+                    None => {
+                        object.insert(name.to_owned(), value.clone());
+                    }
+                }
+            }
+        }
+        if !is_item {
+            collect_rdfa_properties(child, base, object);
+        }
+    }
+}
+
+/// The value of an RDFa Lite property element.
+fn rdfa_value(element: ElementRef, base: &Url) -> String {
+    let value = element.value();
+    if let Some(content) = value.attr("content") {
+        return content.to_owned();
+    }
+    if value.name() == "time"
+        && let Some(datetime) = value.attr("datetime")
+    {
+        return datetime.to_owned();
+    }
+    if let Some(href) = ["resource", "href", "src"]
+        .iter()
+        .find_map(|attr| value.attr(attr))
+    {
+        return base
+            .join(href.trim())
+            .map_or_else(|_| href.to_owned(), String::from);
+    }
+    text_content(element)
+}
+
+/// The text of an element with its whitespace collapsed.
+fn text_content(element: ElementRef) -> String {
+    element
+        .text()
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn element_by_id<'a>(html: &'a Html, id: &str) -> Option<ElementRef<'a>> {
+    html.root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|element| element.value().id() == Some(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn microdata_of(body: &str) -> Vec<Value> {
+        let base = Url::parse("https://example.com/").unwrap();
+        StructuredData::extract(&Html::parse_document(body), &base).microdata
+    }
+
+    #[test]
+    fn itemref_targets_are_expanded_once_per_item() {
+        let items = microdata_of(
+            r#"<div itemscope itemref="name name extra"></div>
+               <p id="name" itemprop="name">Widget</p>
+               <div id="extra"><span itemprop="color">red</span></div>"#,
+        );
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0]["properties"],
+            serde_json::json!({"name": ["Widget"], "color": ["red"]})
+        );
+    }
+
+    #[test]
+    fn itemref_cycles_are_not_followed() {
+        let items = microdata_of(
+            r#"<div itemscope itemref="a b"></div>
+               <div id="a" itemprop="x" itemscope itemref="a b"></div>
+               <div id="b" itemprop="y" itemscope itemref="a b"></div>"#,
+        );
+        assert_eq!(
+            items[0]["properties"],
+            serde_json::json!({
+                "x": [{"properties": {}}],
+                "y": [{"properties": {}}],
+            })
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! # Table Module
+//!
+//! Turns HTML `<table>` elements into rows of text.
+//!
+//! ## Overview
+//!
+//! `Table::from_element` lays a table out on a grid the way a browser does:
+//! a cell spanning several rows or columns with `rowspan` and `colspan` is
+//! repeated in every slot it covers, so that each row ends up with one value
+//! per column. Spans do not cross row groups (`<thead>`, `<tbody>` and
+//! `<tfoot>`).
+//!
+//! The header rows are the rows of the `<thead>`, or, without one, the
+//! leading rows made only of `<th>` cells. When there are several, the names
+//! of a column are joined with spaces, so that a `Price` header spanning
+//! `Min` and `Max` gives the columns `Price Min` and `Price Max`. Footer rows
+//! come last, wherever the `<tfoot>` is in the markup.
+//!
+//! A table can then be turned into JSON records keyed by column name with
+//! `to_records`, or into CSV with `to_csv`. `Response::tables` extracts
+//! every table of a page.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! for table in response.tables() {
+//!     for record in table.to_records() {
+//!         println!("{} costs {}", record["Name"], record["Price"]);
+//!     }
+//! }
+//! ```
+
+use scraper::{ElementRef, Html, Node};
+use serde_json::{Map, Value};
+
+use crate::error::SpiderError;
+use crate::selector_cache::get_cached_selector;
+
+/// The largest `colspan` honored, as in browsers.
+const MAX_COLSPAN: usize = 1000;
+/// The largest `rowspan` honored, as in browsers.
+const MAX_ROWSPAN: usize = 65534;
+
+/// A table laid out on a grid, with its spans expanded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    /// The text of the `<caption>`.
+    pub caption: Option<String>,
+    /// The column names, empty if the table has no header rows.
+    pub headers: Vec<String>,
+    /// The body and footer rows, with one value per column.
+    pub rows: Vec<Vec<String>>,
+}
+
+/// A slot of the grid.
+#[derive(Clone)]
+struct Cell {
+    text: String,
+    header: bool,
+}
+
+type Grid = Vec<Vec<Option<Cell>>>;
+
+impl Table {
+    /// Lays out a `<table>` element.
+    ///
+    /// The rows of tables nested in its cells are left out; their text is
+    /// part of the enclosing cell.
+    pub fn from_element(table: ElementRef<'_>) -> Self {
+        let mut caption = None;
+        let mut head = Grid::new();
+        let mut body = Grid::new();
+        let mut foot = Grid::new();
+        // `<tr>` children outside of a row group, as in XHTML.
+        let mut loose = Vec::new();
+
+        for child in table.children().filter_map(ElementRef::wrap) {
+            let name = child.value().name();
+            if name != "tr" && !loose.is_empty() {
+                body.extend(layout(&loose));
+                loose.clear();
+            }
+            match name {
+                "caption" => {
+                    caption.get_or_insert_with(|| cell_text(child));
+                }
+                "thead" => head.extend(layout(&rows_of(child))),
+                "tbody" => body.extend(layout(&rows_of(child))),
+                "tfoot" => foot.extend(layout(&rows_of(child))),
+                "tr" => loose.push(child),
+                _ => {}
+            }
+        }
+        body.extend(layout(&loose));
+
+        if head.is_empty() {
+            let leading = body
+                .iter()
+                .take_while(|row| !row.is_empty() && row.iter().flatten().all(|cell| cell.header))
+                .count();
+            head = body.drain(..leading).collect();
+        }
+        body.extend(foot);
+        body.retain(|row| !row.is_empty());
+
+        let width = head.iter().chain(&body).map(Vec::len).max().unwrap_or(0);
+        let headers = if head.is_empty() {
+            Vec::new()
+        } else {
+            (0..width).map(|x| column_header(&head, x)).collect()
+        };
+        let rows = body
+            .into_iter()
+            .map(|row| {
+                let mut values: Vec<String> = row
+                    .into_iter()
+                    .map(|cell| cell.map(|cell| cell.text).unwrap_or_default())
+                    .collect();
+                values.resize(width, String::new());
+                values
+            })
+            .collect();
+
+        Self {
+            caption,
+            headers,
+            rows,
+        }
+    }
+
+    /// Returns the names `to_records` keys values with.
+    ///
+    /// Columns without a header are named `column_1`, `column_2` and so on
+    /// after their position, and repeated names get a `_2`, `_3`... suffix.
+    pub fn column_names(&self) -> Vec<String> {
+        let width = self
+            .rows
+            .iter()
+            .map(Vec::len)
+            .chain([self.headers.len()])
+            .max()
+            .unwrap_or(0);
+
+        let mut names: Vec<String> = Vec::with_capacity(width);
+        for x in 0..width {
+            let name = match self.headers.get(x) {
+                Some(header) if !header.is_empty() => header.clone(),
+                _ => format!("column_{}", x + 1),
+            };
+            let mut unique = name.clone();
+            let mut suffix = 2;
+            while names.contains(&unique) {
+                unique = format!("{name}_{suffix}");
+                suffix += 1;
+            }
+            names.push(unique);
+        }
+        names
+    }
+
+    /// Returns the rows as JSON objects keyed by column name, see
+    /// [`Table::column_names`].
+    ///
+    /// The keys of a record are sorted by name, as in any `serde_json` map;
+    /// `column_names` gives them in column order.
+    pub fn to_records(&self) -> Vec<Map<String, Value>> {
+        let names = self.column_names();
+        self.rows
+            .iter()
+            .map(|row| {
+                names
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(|value| Value::String(value.clone())))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Serializes the table as CSV, starting with the headers if it has any.
+    pub fn to_csv(&self) -> Result<String, SpiderError> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        if !self.headers.is_empty() {
+            writer.write_record(&self.headers)?;
+        }
+        for row in &self.rows {
+            writer.write_record(row)?;
+        }
+        let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(String::from_utf8(bytes).map_err(|e| e.utf8_error())?)
+    }
+}
+
+/// Lays out every `<table>` of a document, in document order.
+pub fn extract(html: &Html) -> Vec<Table> {
+    match get_cached_selector("table") {
+        Some(selector) => html.select(&selector).map(Table::from_element).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn rows_of<'a>(group: ElementRef<'a>) -> Vec<ElementRef<'a>> {
+    group
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| child.value().name() == "tr")
+        .collect()
+}
+
+/// Places the cells of a row group on a grid, repeating spanning cells.
+fn layout(rows: &[ElementRef<'_>]) -> Grid {
+    let mut grid: Grid = vec![Vec::new(); rows.len()];
+    for (y, row) in rows.iter().enumerate() {
+        let mut x = 0;
+        let cells = row
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|cell| matches!(cell.value().name(), "td" | "th"));
+        for cell in cells {
+            // Skip the slots taken by cells spanning from the rows above.
+            while grid[y].get(x).is_some_and(Option::is_some) {
+                x += 1;
+            }
+
+            let colspan = span(cell, "colspan").clamp(1, MAX_COLSPAN);
+            // `rowspan="0"` spans the rest of the row group.
+            let rowspan = match span(cell, "rowspan") {
+                0 => rows.len() - y,
+                rowspan => rowspan.min(MAX_ROWSPAN).min(rows.len() - y),
+            };
+            let value = Cell {
+                text: cell_text(cell),
+                header: cell.value().name() == "th",
+            };
+
+            for slots in &mut grid[y..y + rowspan] {
+                if slots.len() < x + colspan {
+                    slots.resize(x + colspan, None);
+                }
+                for slot in &mut slots[x..x + colspan] {
+                    *slot = Some(value.clone());
+                }
+            }
+            x += colspan;
+        }
+    }
+    grid
+}
+
+/// Parses a span attribute, defaulting to 1.
+fn span(cell: ElementRef<'_>, name: &str) -> usize {
+    let Some(value) = cell.value().attr(name) else {
+        return 1;
+    };
+    let value = value.trim_start();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    match value[..digits].parse::<usize>() {
+        Ok(span) => span,
+        Err(_) if digits > 0 => usize::MAX,
+        Err(_) => 1,
+    }
+}
+
+/// Joins the names the header rows give a column.
+fn column_header(head: &Grid, x: usize) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for cell in head.iter().filter_map(|row| row.get(x)?.as_ref()) {
+        // A header spanning several header rows is named once.
+        if !cell.text.is_empty() && parts.last() != Some(&cell.text.as_str()) {
+            parts.push(&cell.text);
+        }
+    }
+    parts.join(" ")
+}
+
+/// Returns the text of an element with its whitespace collapsed, line
+/// breaks and block elements separating words.
+fn cell_text(element: ElementRef<'_>) -> String {
+    let mut text = String::new();
+    for node in element.descendants() {
+        match node.value() {
+            Node::Text(value) => text.push_str(value),
+            Node::Element(child)
+                if matches!(child.name(), "br" | "p" | "div" | "li" | "tr" | "td" | "th") =>
+            {
+                text.push(' ');
+            }
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_table(html: &str) -> Table {
+        extract(&Html::parse_document(html)).remove(0)
+    }
+
+    #[test]
+    fn spans_are_expanded() {
+        let table = first_table(
+            "<table>
+              <thead>
+                <tr><th rowspan=2>Name</th><th colspan=2>Price</th></tr>
+                <tr><th>Min</th><th>Max</th></tr>
+              </thead>
+              <tbody>
+                <tr><td rowspan=2>Widget</td><td>1</td><td>2</td></tr>
+                <tr><td colspan=2>3</td></tr>
+              </tbody>
+            </table>",
+        );
+        assert_eq!(table.headers, ["Name", "Price Min", "Price Max"]);
+        assert_eq!(table.rows, [["Widget", "1", "2"], ["Widget", "3", "3"]]);
+    }
+
+    #[test]
+    fn column_names_keep_the_column_order() {
+        let table = first_table(
+            "<table>
+              <tr><th>Zeta</th><th>Alpha</th><th>Zeta</th><th></th></tr>
+              <tr><td>1</td><td>2</td><td>3</td><td>4</td></tr>
+            </table>",
+        );
+        assert_eq!(
+            table.column_names(),
+            ["Zeta", "Alpha", "Zeta_2", "column_4"]
+        );
+        let records = table.to_records();
+        let values: Vec<&Value> = table
+            .column_names()
+            .iter()
+            .map(|name| &records[0][name])
+            .collect();
+        assert_eq!(values, ["1", "2", "3", "4"]);
+        assert_eq!(table.to_csv().unwrap(), "Zeta,Alpha,Zeta,\n1,2,3,4\n");
+    }
+}
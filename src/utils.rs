@@ -4,6 +4,8 @@
 //! components of the framework.
 
 use psl::{List, Psl};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::BytesRef;
 use scraper::Selector;
 use std::fs;
 use std::path::Path;
@@ -64,3 +66,287 @@ impl ToSelector for String {
     }
 }
 
+/// Controls how [`canonicalize_url`] treats a trailing slash on the URL path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingSlash {
+    /// Leaves the path untouched.
+    #[default]
+    Keep,
+    /// Appends a trailing slash to paths that do not end with one.
+    Add,
+    /// Removes the trailing slash from any path other than the root.
+    Remove,
+}
+
+/// Options for [`canonicalize_url`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalizeOptions {
+    /// Keeps the `#fragment` part of the URL instead of stripping it.
+    pub keep_fragment: bool,
+    /// Sorts query parameters by key, then by value.
+    pub sort_query: bool,
+    /// Keeps query parameters with an empty value (`?a=&b`).
+    pub keep_blank_values: bool,
+    /// Policy applied to a trailing slash on the path.
+    pub trailing_slash: TrailingSlash,
+}
+
+impl Default for CanonicalizeOptions {
+    fn default() -> Self {
+        Self {
+            keep_fragment: false,
+            sort_query: true,
+            keep_blank_values: true,
+            trailing_slash: TrailingSlash::Keep,
+        }
+    }
+}
+
+/// Returns a canonical form of `url`, so that equivalent URLs compare equal.
+///
+/// Parsing with [`Url`] already lowercases the scheme and host of special
+/// schemes, drops default ports and resolves dot-segments. On top of that this
+/// lowercases hosts of non-special schemes, normalizes percent-encoding
+/// (unreserved characters are decoded, remaining escapes are uppercased),
+/// strips the fragment, sorts the query and applies the trailing-slash policy,
+/// all according to `options`.
+pub fn canonicalize_url(url: &Url, options: &CanonicalizeOptions) -> Url {
+    let mut url = url.clone();
+
+    if let Some(host) = url.host_str() {
+        let lowered = host.to_ascii_lowercase();
+        if lowered != host {
+            let _ = url.set_host(Some(&lowered));
+        }
+    }
+
+    if !url.cannot_be_a_base() {
+        let mut path = normalize_percent_encoding(url.path());
+        match options.trailing_slash {
+            TrailingSlash::Keep => {}
+            TrailingSlash::Add => {
+                if !path.ends_with('/') {
+                    path.push('/');
+                }
+            }
+            TrailingSlash::Remove => {
+                while path.len() > 1 && path.ends_with('/') {
+                    path.pop();
+                }
+            }
+        }
+        url.set_path(&path);
+    }
+
+    if let Some(query) = url.query() {
+        let mut params: Vec<(String, Option<String>)> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => (
+                    normalize_percent_encoding(key),
+                    Some(normalize_percent_encoding(value)),
+                ),
+                None => (normalize_percent_encoding(pair), None),
+            })
+            .filter(|(_, value)| {
+                options.keep_blank_values || value.as_deref().is_some_and(|v| !v.is_empty())
+            })
+            .collect();
+
+        if options.sort_query {
+            params.sort();
+        }
+
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            let query = params
+                .iter()
+                .map(|(key, value)| match value {
+                    Some(value) => format!("{key}={value}"),
+                    None => key.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            url.set_query(Some(&query));
+        }
+    }
+
+    if !options.keep_fragment {
+        url.set_fragment(None);
+    } else if let Some(fragment) = url.fragment() {
+        let fragment = normalize_percent_encoding(fragment);
+        url.set_fragment(Some(&fragment));
+    }
+
+    url
+}
+
+/// Decodes percent-encoded unreserved characters and uppercases the hex digits
+/// of every other escape sequence (RFC 3986, section 6.2.2.2).
+pub(crate) fn normalize_percent_encoding(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = String::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2]))
+        {
+            let decoded = (hi << 4) | lo;
+            if decoded.is_ascii_alphanumeric() || matches!(decoded, b'-' | b'.' | b'_' | b'~') {
+                out.push(decoded as char);
+            } else {
+                out.push('%');
+                out.push(bytes[i + 1].to_ascii_uppercase() as char);
+                out.push(bytes[i + 2].to_ascii_uppercase() as char);
+            }
+            i += 3;
+        } else {
+            out.push(bytes[i] as char);
+            i += 1;
+        }
+    }
+
+    out
+}
+
+/// Expands an XML character or predefined entity reference. Unknown entities
+/// are kept as written.
+pub(crate) fn resolve_xml_reference(reference: &BytesRef<'_>) -> String {
+    if let Ok(Some(c)) = reference.resolve_char_ref() {
+        return c.to_string();
+    }
+    match resolve_predefined_entity(reference) {
+        Some(expanded) => expanded.to_owned(),
+        None => format!("&{};", &**reference),
+    }
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|d| d as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(url: &str, options: &CanonicalizeOptions) -> String {
+        canonicalize_url(&Url::parse(url).unwrap(), options).to_string()
+    }
+
+    fn assert_equivalent(options: &CanonicalizeOptions, pairs: &[(&str, &str)]) {
+        for (left, right) in pairs {
+            assert_eq!(
+                canonical(left, options),
+                canonical(right, options),
+                "{left} should be equivalent to {right}"
+            );
+        }
+    }
+
+    fn assert_distinct(options: &CanonicalizeOptions, pairs: &[(&str, &str)]) {
+        for (left, right) in pairs {
+            assert_ne!(
+                canonical(left, options),
+                canonical(right, options),
+                "{left} should differ from {right}"
+            );
+        }
+    }
+
+    fn with_trailing_slash(trailing_slash: TrailingSlash) -> CanonicalizeOptions {
+        CanonicalizeOptions {
+            trailing_slash,
+            ..CanonicalizeOptions::default()
+        }
+    }
+
+    #[test]
+    fn canonicalize_url_equivalent_pairs() {
+        assert_equivalent(
+            &CanonicalizeOptions::default(),
+            &[
+                ("http://a.test:80/x", "http://a.test/x"),
+                ("https://a.test:443/x", "https://a.test/x"),
+                ("https://A.TEST/x", "https://a.test/x"),
+                ("foo://A.TEST/x", "foo://a.test/x"),
+                ("https://a.test/?b=2&a=1", "https://a.test/?a=1&b=2"),
+                ("https://a.test/?a=2&a=1", "https://a.test/?a=1&a=2"),
+                ("https://a.test/%c3%a9", "https://a.test/%C3%A9"),
+                ("https://a.test/?q=%c3%a9", "https://a.test/?q=%C3%A9"),
+                ("https://a.test/%7Euser", "https://a.test/~user"),
+                ("https://a.test/%41%2d", "https://a.test/A-"),
+                ("https://a.test/x#top", "https://a.test/x"),
+                ("https://a.test/x/./y/../z", "https://a.test/x/z"),
+                ("https://a.test/x?", "https://a.test/x"),
+            ],
+        );
+        assert_equivalent(
+            &CanonicalizeOptions {
+                keep_fragment: true,
+                ..CanonicalizeOptions::default()
+            },
+            &[("https://a.test/x#%7e", "https://a.test/x#~")],
+        );
+        assert_equivalent(
+            &CanonicalizeOptions {
+                keep_blank_values: false,
+                ..CanonicalizeOptions::default()
+            },
+            &[("https://a.test/?a=&b=1", "https://a.test/?b=1")],
+        );
+        assert_equivalent(
+            &with_trailing_slash(TrailingSlash::Add),
+            &[
+                ("https://a.test/x", "https://a.test/x/"),
+                ("https://a.test/x/", "https://a.test/x/"),
+            ],
+        );
+        assert_equivalent(
+            &with_trailing_slash(TrailingSlash::Remove),
+            &[
+                ("https://a.test/x/", "https://a.test/x"),
+                ("https://a.test/x//", "https://a.test/x"),
+                ("https://a.test/", "https://a.test"),
+            ],
+        );
+    }
+
+    #[test]
+    fn canonicalize_url_distinct_pairs() {
+        assert_distinct(
+            &CanonicalizeOptions::default(),
+            &[
+                ("https://a.test/Page", "https://a.test/page"),
+                ("https://a.test/x/", "https://a.test/x"),
+                ("https://a.test/?a=1", "https://a.test/?a=2"),
+                ("https://a.test/?a=1", "https://a.test/?b=1"),
+                ("https://a.test/?a=", "https://a.test/?a"),
+                ("https://a.test/%2F", "https://a.test//"),
+                ("http://a.test/x", "https://a.test/x"),
+                ("https://a.test:8443/x", "https://a.test/x"),
+            ],
+        );
+        assert_distinct(
+            &CanonicalizeOptions {
+                keep_fragment: true,
+                ..CanonicalizeOptions::default()
+            },
+            &[("https://a.test/x#one", "https://a.test/x#two")],
+        );
+        assert_distinct(
+            &CanonicalizeOptions {
+                sort_query: false,
+                ..CanonicalizeOptions::default()
+            },
+            &[("https://a.test/?b=2&a=1", "https://a.test/?a=1&b=2")],
+        );
+        assert_distinct(
+            &with_trailing_slash(TrailingSlash::Remove),
+            &[("https://a.test/x/y", "https://a.test/x")],
+        );
+    }
+}
@@ -0,0 +1,1863 @@
+//! # XPath Module
+//!
+//! Evaluates XPath 1.0 expressions against parsed HTML and XML documents.
+//!
+//! ## Overview
+//!
+//! `XPath::compile` parses an expression once so it can be evaluated against
+//! any number of documents. `get_cached_xpath` keeps compiled expressions in a
+//! global cache, the way [`selector_cache`](crate::selector_cache) does for
+//! CSS selectors.
+//!
+//! Expressions are evaluated with `Response::xpath`, which returns an
+//! `XPathValue`, or `SelectorList::xpath`, which evaluates the expression
+//! against each match of a list. Node-sets are returned as a
+//! [`SelectorList`], so they can be refined with further CSS or XPath
+//! queries; selected attributes become their values.
+//!
+//! The whole XPath 1.0 grammar and core function library are supported,
+//! along with Parsel's `has-class()`. Variables are not. Names are matched
+//! without namespace resolution: an unprefixed name matches elements in any
+//! namespace, and a prefixed one matches the prefix written in the document.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! let titles = response.xpath("//h2[contains(@class, 'title')]/text()")?;
+//! let count = response.xpath("count(//li)")?.as_number();
+//!
+//! for row in response.css("table tr")?.iter() {
+//!     let price = row.xpath("td[.='Price']/following-sibling::td[1]/text()")?.get();
+//! }
+//! ```
+
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use ego_tree::{NodeId, NodeRef};
+use html5ever::{Attribute, LocalName, Namespace, Prefix, QualName};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use quick_xml::Reader;
+use quick_xml::XmlVersion;
+use quick_xml::events::{BytesStart, Event};
+use scraper::node::{Comment, Element, ProcessingInstruction, Text};
+use scraper::{Html, Node, StrTendril};
+
+use crate::error::SpiderError;
+use crate::selector::{Item, SelectorList, SharedDocument};
+use crate::utils::resolve_xml_reference;
+
+// Global XPath cache to avoid repeated compilation
+static XPATH_CACHE: Lazy<RwLock<HashMap<String, Arc<XPath>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Get a compiled XPath expression from the cache or compile and store it if not present
+pub fn get_cached_xpath(expr: &str) -> Result<Arc<XPath>, SpiderError> {
+    {
+        let cache = XPATH_CACHE.read();
+        if let Some(cached) = cache.get(expr) {
+            return Ok(Arc::clone(cached));
+        }
+    }
+
+    let xpath = Arc::new(XPath::compile(expr)?);
+    let mut cache = XPATH_CACHE.write();
+    if let Some(cached) = cache.get(expr) {
+        return Ok(Arc::clone(cached));
+    }
+    cache.insert(expr.to_string(), Arc::clone(&xpath));
+    Ok(xpath)
+}
+
+/// A compiled XPath 1.0 expression.
+#[derive(Debug, Clone)]
+pub struct XPath {
+    source: String,
+    expr: Expr,
+}
+
+/// The result of an XPath expression.
+#[derive(Debug, Clone)]
+pub enum XPathValue {
+    Nodes(SelectorList),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+impl XPathValue {
+    /// Returns the selected nodes, if the result is a node-set.
+    pub fn into_nodes(self) -> Option<SelectorList> {
+        match self {
+            XPathValue::Nodes(nodes) => Some(nodes),
+            _ => None,
+        }
+    }
+
+    /// Returns the string, if the result is one.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            XPathValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the number, if the result is one.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            XPathValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the boolean, if the result is one.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            XPathValue::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl XPath {
+    /// Compiles an XPath 1.0 expression.
+    ///
+    /// Returns [`SpiderError::XPathError`] if the expression is invalid.
+    pub fn compile(expr: &str) -> Result<Self, SpiderError> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let parsed = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("unexpected {token:?} in '{expr}'")));
+        }
+        Ok(Self {
+            source: expr.to_owned(),
+            expr: parsed,
+        })
+    }
+
+    /// Returns the expression this was compiled from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    fn evaluate_at(
+        &self,
+        evaluator: &Evaluator<'_>,
+        context: NodeId,
+    ) -> Result<Value, SpiderError> {
+        let context = Context {
+            node: XNode::Node(context),
+            position: 1,
+            size: 1,
+        };
+        evaluator.eval(&self.expr, &context)
+    }
+}
+
+/// Evaluates `xpath` against the root of `document`.
+pub(crate) fn evaluate(document: SharedDocument, xpath: &XPath) -> Result<XPathValue, SpiderError> {
+    let html = document.lock();
+    let evaluator = Evaluator::new(&html);
+    let value = xpath.evaluate_at(&evaluator, html.tree.root().id())?;
+    Ok(match value {
+        Value::Nodes(nodes) => {
+            let items = evaluator.items(nodes);
+            drop(html);
+            XPathValue::Nodes(SelectorList::from_items(document, items))
+        }
+        Value::String(value) => XPathValue::String(value),
+        Value::Number(value) => XPathValue::Number(value),
+        Value::Boolean(value) => XPathValue::Boolean(value),
+    })
+}
+
+/// Evaluates `xpath` with each of `contexts` as the context node.
+///
+/// Node-sets are merged in document order; other results are returned as
+/// text, in the order of the contexts.
+pub(crate) fn select(
+    html: &Html,
+    xpath: &XPath,
+    contexts: &[NodeId],
+) -> Result<Vec<Item>, SpiderError> {
+    let evaluator = Evaluator::new(html);
+    let mut nodes = Vec::new();
+    let mut texts = Vec::new();
+    for &context in contexts {
+        match xpath.evaluate_at(&evaluator, context)? {
+            Value::Nodes(found) => nodes.extend(found),
+            other => texts.push(Item::Text(evaluator.string(&other))),
+        }
+    }
+
+    let nodes = if contexts.len() > 1 {
+        evaluator.document_order(nodes)
+    } else {
+        nodes
+    };
+    let mut items = evaluator.items(nodes);
+    items.extend(texts);
+    Ok(items)
+}
+
+/// Parses an XML document into the same tree structure as HTML documents, so
+/// it can be queried with XPath.
+///
+/// Text outside the root element, the XML declaration and the doctype are
+/// dropped, as are namespace declarations.
+pub(crate) fn parse_xml(text: &str) -> Result<Html, SpiderError> {
+    let mut html = Html::new_document();
+    let root = html.tree.root().id();
+    let mut stack = vec![root];
+    let mut reader = Reader::from_str(text);
+
+    loop {
+        let parent = *stack.last().unwrap_or(&root);
+        match reader.read_event()? {
+            Event::Start(start) => {
+                let element = xml_element(&start)?;
+                stack.push(append(&mut html, parent, element));
+            }
+            Event::Empty(start) => {
+                let element = xml_element(&start)?;
+                append(&mut html, parent, element);
+            }
+            Event::End(_) if stack.len() > 1 => {
+                stack.pop();
+            }
+            Event::Text(content) if parent != root => {
+                append_text(&mut html, parent, &content.xml10_content());
+            }
+            Event::CData(content) if parent != root => {
+                append_text(&mut html, parent, &content.xml10_content());
+            }
+            Event::GeneralRef(reference) if parent != root => {
+                append_text(&mut html, parent, &resolve_xml_reference(&reference));
+            }
+            Event::Comment(content) => {
+                let comment = Node::Comment(Comment {
+                    comment: StrTendril::from(&*content.xml10_content()),
+                });
+                append(&mut html, parent, comment);
+            }
+            Event::PI(pi) => {
+                let pi = Node::ProcessingInstruction(ProcessingInstruction {
+                    target: StrTendril::from(pi.target()),
+                    data: StrTendril::from(pi.content().trim_start()),
+                });
+                append(&mut html, parent, pi);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if stack.len() > 1 {
+        return Err(SpiderError::XmlParseError(
+            "unexpected end of document".to_string(),
+        ));
+    }
+    Ok(html)
+}
+
+fn xml_element(start: &BytesStart<'_>) -> Result<Node, SpiderError> {
+    let mut attributes = Vec::new();
+    for attr in start.attributes() {
+        let attr = attr.map_err(quick_xml::Error::from)?;
+        let key = attr.key.as_ref();
+        if key == "xmlns" || key.starts_with("xmlns:") {
+            continue;
+        }
+        let value = attr
+            .normalized_value(XmlVersion::Implicit1_0)
+            .map_err(|e| SpiderError::XmlParseError(e.to_string()))?;
+        attributes.push(Attribute {
+            name: qual_name(key),
+            value: (*value).into(),
+        });
+    }
+    Ok(Node::Element(Element::new(
+        qual_name(start.name().as_ref()),
+        attributes,
+    )))
+}
+
+fn qual_name(name: &str) -> QualName {
+    match name.split_once(':') {
+        Some((prefix, local)) => QualName::new(
+            Some(Prefix::from(prefix)),
+            Namespace::from(""),
+            LocalName::from(local),
+        ),
+        None => QualName::new(None, Namespace::from(""), LocalName::from(name)),
+    }
+}
+
+fn append(html: &mut Html, parent: NodeId, node: Node) -> NodeId {
+    match html.tree.get_mut(parent) {
+        Some(mut parent) => parent.append(node).id(),
+        None => parent,
+    }
+}
+
+/// Appends text to `parent`, merging it with a preceding text node.
+fn append_text(html: &mut Html, parent: NodeId, text: &str) {
+    let Some(mut parent) = html.tree.get_mut(parent) else {
+        return;
+    };
+    if let Some(mut last) = parent.last_child()
+        && let Node::Text(existing) = last.value()
+    {
+        existing.text.push_slice(text);
+        return;
+    }
+    parent.append(Node::Text(Text {
+        text: StrTendril::from(text),
+    }));
+}
+
+fn error(message: String) -> SpiderError {
+    SpiderError::XPathError(message)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Slash,
+    DoubleSlash,
+    LeftBracket,
+    RightBracket,
+    LeftParen,
+    RightParen,
+    At,
+    Comma,
+    DoubleColon,
+    Dot,
+    DoubleDot,
+    Pipe,
+    Plus,
+    Minus,
+    Star,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Literal(String),
+    Number(f64),
+    Name(String),
+    Variable(String),
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, SpiderError> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        chars.next();
+        let next = chars.peek().map(|&(_, c)| c);
+        let token = match c {
+            ' ' | '\t' | '\r' | '\n' => continue,
+            '/' if next == Some('/') => {
+                chars.next();
+                Token::DoubleSlash
+            }
+            '/' => Token::Slash,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '@' => Token::At,
+            ',' => Token::Comma,
+            '|' => Token::Pipe,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '=' => Token::Equal,
+            ':' if next == Some(':') => {
+                chars.next();
+                Token::DoubleColon
+            }
+            '!' if next == Some('=') => {
+                chars.next();
+                Token::NotEqual
+            }
+            '<' if next == Some('=') => {
+                chars.next();
+                Token::LessEqual
+            }
+            '<' => Token::Less,
+            '>' if next == Some('=') => {
+                chars.next();
+                Token::GreaterEqual
+            }
+            '>' => Token::Greater,
+            '.' if next == Some('.') => {
+                chars.next();
+                Token::DoubleDot
+            }
+            '.' if !next.is_some_and(|c| c.is_ascii_digit()) => Token::Dot,
+            '"' | '\'' => {
+                let rest = &expr[start + 1..];
+                let end = rest
+                    .find(c)
+                    .ok_or_else(|| error(format!("unterminated string in '{expr}'")))?;
+                for _ in rest[..=end].chars() {
+                    chars.next();
+                }
+                Token::Literal(rest[..end].to_owned())
+            }
+            '0'..='9' | '.' => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_digit() || (c == '.' && !expr[start..i].contains('.'))) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let number = expr[start..end]
+                    .parse()
+                    .map_err(|_| error(format!("invalid number in '{expr}'")))?;
+                Token::Number(number)
+            }
+            '$' => {
+                let name = read_name(expr, &mut chars)
+                    .ok_or_else(|| error(format!("expected a variable name in '{expr}'")))?;
+                Token::Variable(name)
+            }
+            c if is_name_start(c) => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !is_name_char(c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let mut name = expr[start..end].to_owned();
+
+                // A single colon joins a prefix to a local name or `*`.
+                if expr[end..].starts_with(':') && !expr[end..].starts_with("::") {
+                    let after = expr[end + 1..].chars().next();
+                    if after == Some('*') {
+                        chars.next();
+                        chars.next();
+                        name.push_str(":*");
+                    } else if after.is_some_and(is_name_start) {
+                        chars.next();
+                        if let Some(local) = read_name(expr, &mut chars) {
+                            name.push(':');
+                            name.push_str(&local);
+                        }
+                    }
+                }
+                Token::Name(name)
+            }
+            other => return Err(error(format!("unexpected character '{other}' in '{expr}'"))),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn read_name(
+    expr: &str,
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+) -> Option<String> {
+    let &(start, c) = chars.peek()?;
+    if !is_name_start(c) {
+        return None;
+    }
+    let mut end = start;
+    while let Some(&(i, c)) = chars.peek() {
+        if !is_name_char(c) {
+            break;
+        }
+        end = i + c.len_utf8();
+        chars.next();
+    }
+    Some(expr[start..end].to_owned())
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '\u{b7}')
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    Arithmetic(ArithmeticOp, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    /// The root node of the document.
+    Root,
+    /// The context node.
+    ContextNode,
+    /// Location steps applied to the node-set of an expression.
+    Path(Box<Expr>, Vec<Step>),
+    /// Predicates applied to the node-set of an expression.
+    Filter(Box<Expr>, Vec<Expr>),
+    Literal(String),
+    Number(f64),
+    Function(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Namespace,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    SelfNode,
+}
+
+impl Axis {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "ancestor" => Axis::Ancestor,
+            "ancestor-or-self" => Axis::AncestorOrSelf,
+            "attribute" => Axis::Attribute,
+            "child" => Axis::Child,
+            "descendant" => Axis::Descendant,
+            "descendant-or-self" => Axis::DescendantOrSelf,
+            "following" => Axis::Following,
+            "following-sibling" => Axis::FollowingSibling,
+            "namespace" => Axis::Namespace,
+            "parent" => Axis::Parent,
+            "preceding" => Axis::Preceding,
+            "preceding-sibling" => Axis::PrecedingSibling,
+            "self" => Axis::SelfNode,
+            _ => return None,
+        })
+    }
+
+    /// Whether the axis lists nodes in reverse document order.
+    fn is_reverse(self) -> bool {
+        matches!(
+            self,
+            Axis::Ancestor | Axis::AncestorOrSelf | Axis::Preceding | Axis::PrecedingSibling
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+enum NodeTest {
+    /// `node()`
+    Node,
+    /// `text()`
+    Text,
+    /// `comment()`
+    Comment,
+    /// `processing-instruction()`, with an optional target.
+    ProcessingInstruction(Option<String>),
+    /// `*`
+    Any,
+    /// `prefix:*`
+    Prefix(String),
+    /// A name, with an optional prefix.
+    Name(Option<String>, String),
+}
+
+const NODE_TYPES: &[&str] = &["node", "text", "comment", "processing-instruction"];
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Last,
+    Position,
+    Count,
+    Id,
+    LocalName,
+    NamespaceUri,
+    Name,
+    String,
+    Concat,
+    StartsWith,
+    Contains,
+    SubstringBefore,
+    SubstringAfter,
+    Substring,
+    StringLength,
+    NormalizeSpace,
+    Translate,
+    Boolean,
+    Not,
+    True,
+    False,
+    Lang,
+    Number,
+    Sum,
+    Floor,
+    Ceiling,
+    Round,
+    HasClass,
+}
+
+impl Function {
+    /// Returns the function with its minimum and maximum number of arguments.
+    fn from_name(name: &str) -> Option<(Self, usize, usize)> {
+        Some(match name {
+            "last" => (Function::Last, 0, 0),
+            "position" => (Function::Position, 0, 0),
+            "count" => (Function::Count, 1, 1),
+            "id" => (Function::Id, 1, 1),
+            "local-name" => (Function::LocalName, 0, 1),
+            "namespace-uri" => (Function::NamespaceUri, 0, 1),
+            "name" => (Function::Name, 0, 1),
+            "string" => (Function::String, 0, 1),
+            "concat" => (Function::Concat, 2, usize::MAX),
+            "starts-with" => (Function::StartsWith, 2, 2),
+            "contains" => (Function::Contains, 2, 2),
+            "substring-before" => (Function::SubstringBefore, 2, 2),
+            "substring-after" => (Function::SubstringAfter, 2, 2),
+            "substring" => (Function::Substring, 2, 3),
+            "string-length" => (Function::StringLength, 0, 1),
+            "normalize-space" => (Function::NormalizeSpace, 0, 1),
+            "translate" => (Function::Translate, 3, 3),
+            "boolean" => (Function::Boolean, 1, 1),
+            "not" => (Function::Not, 1, 1),
+            "true" => (Function::True, 0, 0),
+            "false" => (Function::False, 0, 0),
+            "lang" => (Function::Lang, 1, 1),
+            "number" => (Function::Number, 0, 1),
+            "sum" => (Function::Sum, 1, 1),
+            "floor" => (Function::Floor, 1, 1),
+            "ceiling" => (Function::Ceiling, 1, 1),
+            "round" => (Function::Round, 1, 1),
+            "has-class" => (Function::HasClass, 1, usize::MAX),
+            _ => return None,
+        })
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes an operator name such as `and` or `div`.
+    fn eat_operator(&mut self, name: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Name(n)) if n == name) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), SpiderError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("{token:?}")))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> SpiderError {
+        match self.peek() {
+            Some(token) => error(format!("expected {expected}, found {token:?}")),
+            None => error(format!("expected {expected}, found end of expression")),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, SpiderError> {
+        let mut expr = self.and_expr()?;
+        while self.eat_operator("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, SpiderError> {
+        let mut expr = self.equality_expr()?;
+        while self.eat_operator("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.equality_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn equality_expr(&mut self) -> Result<Expr, SpiderError> {
+        let mut expr = self.relational_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Equal) => CompareOp::Equal,
+                Some(Token::NotEqual) => CompareOp::NotEqual,
+                _ => return Ok(expr),
+            };
+            self.pos += 1;
+            expr = Expr::Compare(op, Box::new(expr), Box::new(self.relational_expr()?));
+        }
+    }
+
+    fn relational_expr(&mut self) -> Result<Expr, SpiderError> {
+        let mut expr = self.additive_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Less) => CompareOp::Less,
+                Some(Token::LessEqual) => CompareOp::LessEqual,
+                Some(Token::Greater) => CompareOp::Greater,
+                Some(Token::GreaterEqual) => CompareOp::GreaterEqual,
+                _ => return Ok(expr),
+            };
+            self.pos += 1;
+            expr = Expr::Compare(op, Box::new(expr), Box::new(self.additive_expr()?));
+        }
+    }
+
+    fn additive_expr(&mut self) -> Result<Expr, SpiderError> {
+        let mut expr = self.multiplicative_expr()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => ArithmeticOp::Add,
+                Some(Token::Minus) => ArithmeticOp::Subtract,
+                _ => return Ok(expr),
+            };
+            self.pos += 1;
+            expr = Expr::Arithmetic(op, Box::new(expr), Box::new(self.multiplicative_expr()?));
+        }
+    }
+
+    fn multiplicative_expr(&mut self) -> Result<Expr, SpiderError> {
+        let mut expr = self.unary_expr()?;
+        loop {
+            let op = if self.eat(&Token::Star) {
+                ArithmeticOp::Multiply
+            } else if self.eat_operator("div") {
+                ArithmeticOp::Divide
+            } else if self.eat_operator("mod") {
+                ArithmeticOp::Modulo
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Arithmetic(op, Box::new(expr), Box::new(self.unary_expr()?));
+        }
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr, SpiderError> {
+        if self.eat(&Token::Minus) {
+            return Ok(Expr::Negate(Box::new(self.unary_expr()?)));
+        }
+        let mut expr = self.path_expr()?;
+        while self.eat(&Token::Pipe) {
+            expr = Expr::Union(Box::new(expr), Box::new(self.path_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn path_expr(&mut self) -> Result<Expr, SpiderError> {
+        match self.peek() {
+            Some(Token::Slash) => {
+                self.pos += 1;
+                let steps = if self.at_step() {
+                    self.relative_path(Vec::new())?
+                } else {
+                    Vec::new()
+                };
+                Ok(Expr::Path(Box::new(Expr::Root), steps))
+            }
+            Some(Token::DoubleSlash) => {
+                self.pos += 1;
+                let steps = self.relative_path(vec![descendant_or_self()])?;
+                Ok(Expr::Path(Box::new(Expr::Root), steps))
+            }
+            Some(Token::Literal(_) | Token::Number(_) | Token::LeftParen | Token::Variable(_)) => {
+                self.filter_expr()
+            }
+            Some(Token::Name(name))
+                if self.peek_at(1) == Some(&Token::LeftParen)
+                    && !NODE_TYPES.contains(&name.as_str()) =>
+            {
+                self.filter_expr()
+            }
+            _ => {
+                let steps = self.relative_path(Vec::new())?;
+                Ok(Expr::Path(Box::new(Expr::ContextNode), steps))
+            }
+        }
+    }
+
+    fn filter_expr(&mut self) -> Result<Expr, SpiderError> {
+        let primary = self.primary_expr()?;
+        let predicates = self.predicates()?;
+        let expr = if predicates.is_empty() {
+            primary
+        } else {
+            Expr::Filter(Box::new(primary), predicates)
+        };
+
+        let steps = match self.peek() {
+            Some(Token::Slash) => {
+                self.pos += 1;
+                self.relative_path(Vec::new())?
+            }
+            Some(Token::DoubleSlash) => {
+                self.pos += 1;
+                self.relative_path(vec![descendant_or_self()])?
+            }
+            _ => return Ok(expr),
+        };
+        Ok(Expr::Path(Box::new(expr), steps))
+    }
+
+    fn primary_expr(&mut self) -> Result<Expr, SpiderError> {
+        match self.peek().cloned() {
+            Some(Token::Literal(value)) => {
+                self.pos += 1;
+                Ok(Expr::Literal(value))
+            }
+            Some(Token::Number(value)) => {
+                self.pos += 1;
+                Ok(Expr::Number(value))
+            }
+            Some(Token::LeftParen) => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(&Token::RightParen)?;
+                Ok(expr)
+            }
+            Some(Token::Variable(name)) => {
+                Err(error(format!("variables are not supported: ${name}")))
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 2;
+                let mut args = Vec::new();
+                if !self.eat(&Token::RightParen) {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(&Token::RightParen) {
+                            break;
+                        }
+                        self.expect(&Token::Comma)?;
+                    }
+                }
+
+                let (function, min, max) = Function::from_name(&name)
+                    .ok_or_else(|| error(format!("unknown function {name}()")))?;
+                if args.len() < min || args.len() > max {
+                    return Err(error(format!(
+                        "wrong number of arguments to {name}(): {}",
+                        args.len()
+                    )));
+                }
+                Ok(Expr::Function(function, args))
+            }
+            _ => Err(self.unexpected("an expression")),
+        }
+    }
+
+    fn at_step(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Dot | Token::DoubleDot | Token::At | Token::Star | Token::Name(_))
+        )
+    }
+
+    /// Parses the steps of a relative location path, appending them to `steps`.
+    fn relative_path(&mut self, mut steps: Vec<Step>) -> Result<Vec<Step>, SpiderError> {
+        steps.push(self.step()?);
+        loop {
+            match self.peek() {
+                Some(Token::Slash) => {
+                    self.pos += 1;
+                }
+                Some(Token::DoubleSlash) => {
+                    self.pos += 1;
+                    steps.push(descendant_or_self());
+                }
+                _ => break,
+            }
+            steps.push(self.step()?);
+        }
+        Ok(simplify(steps))
+    }
+
+    fn step(&mut self) -> Result<Step, SpiderError> {
+        if self.eat(&Token::Dot) {
+            return Ok(Step {
+                axis: Axis::SelfNode,
+                test: NodeTest::Node,
+                predicates: Vec::new(),
+            });
+        }
+        if self.eat(&Token::DoubleDot) {
+            return Ok(Step {
+                axis: Axis::Parent,
+                test: NodeTest::Node,
+                predicates: Vec::new(),
+            });
+        }
+
+        let axis = if self.eat(&Token::At) {
+            Axis::Attribute
+        } else if let Some(Token::Name(name)) = self.peek()
+            && self.peek_at(1) == Some(&Token::DoubleColon)
+        {
+            let axis =
+                Axis::from_name(name).ok_or_else(|| error(format!("unknown axis {name}")))?;
+            self.pos += 2;
+            axis
+        } else {
+            Axis::Child
+        };
+
+        let test = self.node_test()?;
+        let predicates = self.predicates()?;
+        Ok(Step {
+            axis,
+            test,
+            predicates,
+        })
+    }
+
+    fn node_test(&mut self) -> Result<NodeTest, SpiderError> {
+        if self.eat(&Token::Star) {
+            return Ok(NodeTest::Any);
+        }
+        let Some(Token::Name(name)) = self.peek().cloned() else {
+            return Err(self.unexpected("a node test"));
+        };
+        self.pos += 1;
+
+        if self.eat(&Token::LeftParen) {
+            let test = match name.as_str() {
+                "node" => NodeTest::Node,
+                "text" => NodeTest::Text,
+                "comment" => NodeTest::Comment,
+                "processing-instruction" => match self.peek().cloned() {
+                    Some(Token::Literal(target)) => {
+                        self.pos += 1;
+                        NodeTest::ProcessingInstruction(Some(target))
+                    }
+                    _ => NodeTest::ProcessingInstruction(None),
+                },
+                _ => return Err(error(format!("unexpected function {name}() in a path"))),
+            };
+            self.expect(&Token::RightParen)?;
+            return Ok(test);
+        }
+
+        Ok(match name.split_once(':') {
+            Some((prefix, "*")) => NodeTest::Prefix(prefix.to_owned()),
+            Some((prefix, local)) => NodeTest::Name(Some(prefix.to_owned()), local.to_owned()),
+            None => NodeTest::Name(None, name),
+        })
+    }
+
+    fn predicates(&mut self) -> Result<Vec<Expr>, SpiderError> {
+        let mut predicates = Vec::new();
+        while self.eat(&Token::LeftBracket) {
+            predicates.push(self.expr()?);
+            self.expect(&Token::RightBracket)?;
+        }
+        Ok(predicates)
+    }
+}
+
+/// The step `//` abbreviates.
+fn descendant_or_self() -> Step {
+    Step {
+        axis: Axis::DescendantOrSelf,
+        test: NodeTest::Node,
+        predicates: Vec::new(),
+    }
+}
+
+/// Rewrites `descendant-or-self::node()/child::x` as `descendant::x`, which
+/// selects the same nodes when the child step has no predicates.
+fn simplify(steps: Vec<Step>) -> Vec<Step> {
+    let mut simplified: Vec<Step> = Vec::with_capacity(steps.len());
+    for mut step in steps {
+        if step.axis == Axis::Child
+            && step.predicates.is_empty()
+            && let Some(last) = simplified.last()
+            && last.axis == Axis::DescendantOrSelf
+            && matches!(last.test, NodeTest::Node)
+            && last.predicates.is_empty()
+        {
+            simplified.pop();
+            step.axis = Axis::Descendant;
+        }
+        simplified.push(step);
+    }
+    simplified
+}
+
+/// A node in the XPath data model: a node of the tree, or an attribute of an
+/// element, by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum XNode {
+    Node(NodeId),
+    Attribute(NodeId, usize),
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    /// Nodes in document order, without duplicates.
+    Nodes(Vec<XNode>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+struct Context {
+    node: XNode,
+    position: usize,
+    size: usize,
+}
+
+struct Evaluator<'a> {
+    html: &'a Html,
+    order: OnceCell<HashMap<NodeId, usize>>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(html: &'a Html) -> Self {
+        Self {
+            html,
+            order: OnceCell::new(),
+        }
+    }
+
+    fn get(&self, id: NodeId) -> Option<NodeRef<'a, Node>> {
+        self.html.tree.get(id)
+    }
+
+    fn eval(&self, expr: &Expr, context: &Context) -> Result<Value, SpiderError> {
+        Ok(match expr {
+            Expr::Or(left, right) => Value::Boolean(
+                self.boolean(&self.eval(left, context)?)
+                    || self.boolean(&self.eval(right, context)?),
+            ),
+            Expr::And(left, right) => Value::Boolean(
+                self.boolean(&self.eval(left, context)?)
+                    && self.boolean(&self.eval(right, context)?),
+            ),
+            Expr::Compare(op, left, right) => {
+                let left = self.eval(left, context)?;
+                let right = self.eval(right, context)?;
+                Value::Boolean(self.compare(*op, &left, &right))
+            }
+            Expr::Arithmetic(op, left, right) => {
+                let left = self.number(&self.eval(left, context)?);
+                let right = self.number(&self.eval(right, context)?);
+                Value::Number(match op {
+                    ArithmeticOp::Add => left + right,
+                    ArithmeticOp::Subtract => left - right,
+                    ArithmeticOp::Multiply => left * right,
+                    ArithmeticOp::Divide => left / right,
+                    ArithmeticOp::Modulo => left % right,
+                })
+            }
+            Expr::Negate(expr) => Value::Number(-self.number(&self.eval(expr, context)?)),
+            Expr::Union(left, right) => {
+                let mut nodes = self.node_set(left, context)?;
+                nodes.extend(self.node_set(right, context)?);
+                Value::Nodes(self.document_order(nodes))
+            }
+            Expr::Root => Value::Nodes(vec![XNode::Node(self.html.tree.root().id())]),
+            Expr::ContextNode => Value::Nodes(vec![context.node]),
+            Expr::Path(start, steps) => {
+                let mut nodes = self.node_set(start, context)?;
+                for step in steps {
+                    nodes = self.step(&nodes, step)?;
+                }
+                Value::Nodes(nodes)
+            }
+            Expr::Filter(primary, predicates) => {
+                let nodes = self.node_set(primary, context)?;
+                Value::Nodes(self.filter(nodes, predicates)?)
+            }
+            Expr::Literal(value) => Value::String(value.clone()),
+            Expr::Number(value) => Value::Number(*value),
+            Expr::Function(function, args) => self.call(*function, args, context)?,
+        })
+    }
+
+    fn node_set(&self, expr: &Expr, context: &Context) -> Result<Vec<XNode>, SpiderError> {
+        match self.eval(expr, context)? {
+            Value::Nodes(nodes) => Ok(nodes),
+            other => Err(error(format!("expected a node-set, found {other:?}"))),
+        }
+    }
+
+    /// Applies a location step to each node of `nodes`.
+    fn step(&self, nodes: &[XNode], step: &Step) -> Result<Vec<XNode>, SpiderError> {
+        let mut selected = Vec::new();
+        for &node in nodes {
+            let candidates = self
+                .axis(node, step.axis)
+                .into_iter()
+                .filter(|&candidate| self.matches(candidate, step.axis, &step.test))
+                .collect();
+            selected.extend(self.filter(candidates, &step.predicates)?);
+        }
+
+        Ok(if nodes.len() > 1 {
+            self.document_order(selected)
+        } else {
+            if step.axis.is_reverse() {
+                selected.reverse();
+            }
+            selected
+        })
+    }
+
+    /// Keeps the nodes that satisfy every predicate, in the given order.
+    fn filter(
+        &self,
+        mut nodes: Vec<XNode>,
+        predicates: &[Expr],
+    ) -> Result<Vec<XNode>, SpiderError> {
+        for predicate in predicates {
+            let size = nodes.len();
+            let mut kept = Vec::new();
+            for (index, node) in nodes.into_iter().enumerate() {
+                let context = Context {
+                    node,
+                    position: index + 1,
+                    size,
+                };
+                let keep = match self.eval(predicate, &context)? {
+                    Value::Number(position) => position == context.position as f64,
+                    other => self.boolean(&other),
+                };
+                if keep {
+                    kept.push(node);
+                }
+            }
+            nodes = kept;
+        }
+        Ok(nodes)
+    }
+
+    /// Lists the nodes on an axis, closest first.
+    fn axis(&self, node: XNode, axis: Axis) -> Vec<XNode> {
+        let (id, attribute) = match node {
+            XNode::Node(id) => (id, false),
+            XNode::Attribute(id, _) => (id, true),
+        };
+        let Some(tree_node) = self.get(id) else {
+            return Vec::new();
+        };
+        let wrap = |node: NodeRef<'a, Node>| XNode::Node(node.id());
+
+        if attribute {
+            return match axis {
+                Axis::SelfNode => vec![node],
+                Axis::Parent => vec![XNode::Node(id)],
+                Axis::Ancestor => std::iter::once(tree_node)
+                    .chain(tree_node.ancestors())
+                    .map(wrap)
+                    .collect(),
+                Axis::AncestorOrSelf => std::iter::once(node)
+                    .chain(
+                        std::iter::once(tree_node)
+                            .chain(tree_node.ancestors())
+                            .map(wrap),
+                    )
+                    .collect(),
+                Axis::Following => tree_node
+                    .descendants()
+                    .skip(1)
+                    .map(wrap)
+                    .chain(self.axis(XNode::Node(id), Axis::Following))
+                    .collect(),
+                Axis::Preceding => self.axis(XNode::Node(id), Axis::Preceding),
+                _ => Vec::new(),
+            };
+        }
+
+        match axis {
+            Axis::Child => tree_node.children().map(wrap).collect(),
+            Axis::Descendant => tree_node.descendants().skip(1).map(wrap).collect(),
+            Axis::DescendantOrSelf => tree_node.descendants().map(wrap).collect(),
+            Axis::Parent => tree_node.parent().map(wrap).into_iter().collect(),
+            Axis::Ancestor => tree_node.ancestors().map(wrap).collect(),
+            Axis::AncestorOrSelf => std::iter::once(tree_node)
+                .chain(tree_node.ancestors())
+                .map(wrap)
+                .collect(),
+            Axis::FollowingSibling => tree_node.next_siblings().map(wrap).collect(),
+            Axis::PrecedingSibling => tree_node.prev_siblings().map(wrap).collect(),
+            Axis::Following => std::iter::once(tree_node)
+                .chain(tree_node.ancestors())
+                .flat_map(|node| node.next_siblings())
+                .flat_map(|sibling| sibling.descendants())
+                .map(wrap)
+                .collect(),
+            Axis::Preceding => {
+                let mut nodes = Vec::new();
+                for node in std::iter::once(tree_node).chain(tree_node.ancestors()) {
+                    for sibling in node.prev_siblings() {
+                        let subtree: Vec<_> = sibling.descendants().map(wrap).collect();
+                        nodes.extend(subtree.into_iter().rev());
+                    }
+                }
+                nodes
+            }
+            Axis::Attribute => match tree_node.value() {
+                Node::Element(element) => (0..element.attrs.len())
+                    .map(|index| XNode::Attribute(id, index))
+                    .collect(),
+                _ => Vec::new(),
+            },
+            Axis::SelfNode => vec![node],
+            Axis::Namespace => Vec::new(),
+        }
+    }
+
+    fn matches(&self, node: XNode, axis: Axis, test: &NodeTest) -> bool {
+        let name = match node {
+            XNode::Attribute(..) if axis != Axis::Attribute => {
+                return matches!(test, NodeTest::Node);
+            }
+            XNode::Attribute(id, index) => match self.attribute(id, index) {
+                Some((name, _)) => name,
+                None => return false,
+            },
+            XNode::Node(id) => {
+                let Some(node) = self.get(id) else {
+                    return false;
+                };
+                match (node.value(), test) {
+                    (Node::Doctype(_), _) => return false,
+                    (_, NodeTest::Node) => return true,
+                    (Node::Text(_), NodeTest::Text) => return true,
+                    (Node::Comment(_), NodeTest::Comment) => return true,
+                    (Node::ProcessingInstruction(pi), NodeTest::ProcessingInstruction(target)) => {
+                        return target.as_deref().is_none_or(|target| *pi.target == *target);
+                    }
+                    (Node::Element(element), _) if axis != Axis::Attribute => &element.name,
+                    _ => return false,
+                }
+            }
+        };
+
+        match test {
+            NodeTest::Node | NodeTest::Any => true,
+            NodeTest::Prefix(prefix) => name.prefix.as_deref() == Some(prefix.as_str()),
+            NodeTest::Name(prefix, local) => {
+                *name.local == **local
+                    && prefix
+                        .as_deref()
+                        .is_none_or(|prefix| name.prefix.as_deref() == Some(prefix))
+            }
+            _ => false,
+        }
+    }
+
+    fn attribute(&self, id: NodeId, index: usize) -> Option<(&'a QualName, &'a str)> {
+        match self.get(id)?.value() {
+            Node::Element(element) => element
+                .attrs
+                .iter()
+                .nth(index)
+                .map(|(name, value)| (name, &**value)),
+            _ => None,
+        }
+    }
+
+    /// Sorts nodes in document order and removes duplicates.
+    fn document_order(&self, mut nodes: Vec<XNode>) -> Vec<XNode> {
+        if nodes.len() < 2 {
+            return nodes;
+        }
+        let order = self.order.get_or_init(|| {
+            self.html
+                .tree
+                .root()
+                .descendants()
+                .enumerate()
+                .map(|(index, node)| (node.id(), index))
+                .collect()
+        });
+        nodes.sort_by_key(|node| match node {
+            XNode::Node(id) => (order.get(id).copied().unwrap_or(usize::MAX), 0),
+            XNode::Attribute(id, index) => {
+                (order.get(id).copied().unwrap_or(usize::MAX), index + 1)
+            }
+        });
+        nodes.dedup();
+        nodes
+    }
+
+    /// Converts nodes to selector list items, attributes becoming their
+    /// values.
+    fn items(&self, nodes: Vec<XNode>) -> Vec<Item> {
+        nodes
+            .into_iter()
+            .map(|node| match node {
+                XNode::Node(id) => Item::Node(id),
+                XNode::Attribute(..) => Item::Text(self.string_value(node)),
+            })
+            .collect()
+    }
+
+    fn string_value(&self, node: XNode) -> String {
+        match node {
+            XNode::Attribute(id, index) => self
+                .attribute(id, index)
+                .map(|(_, value)| value.to_owned())
+                .unwrap_or_default(),
+            XNode::Node(id) => {
+                let Some(node) = self.get(id) else {
+                    return String::new();
+                };
+                match node.value() {
+                    Node::Text(text) => text.text.to_string(),
+                    Node::Comment(comment) => comment.comment.to_string(),
+                    Node::ProcessingInstruction(pi) => pi.data.to_string(),
+                    Node::Doctype(_) => String::new(),
+                    _ => node
+                        .descendants()
+                        .filter_map(|node| match node.value() {
+                            Node::Text(text) => Some(&**text),
+                            _ => None,
+                        })
+                        .collect(),
+                }
+            }
+        }
+    }
+
+    fn string(&self, value: &Value) -> String {
+        match value {
+            Value::Nodes(nodes) => nodes
+                .first()
+                .map(|&node| self.string_value(node))
+                .unwrap_or_default(),
+            Value::String(value) => value.clone(),
+            Value::Number(value) => number_to_string(*value),
+            Value::Boolean(value) => value.to_string(),
+        }
+    }
+
+    fn number(&self, value: &Value) -> f64 {
+        match value {
+            Value::Nodes(_) => string_to_number(&self.string(value)),
+            Value::String(value) => string_to_number(value),
+            Value::Number(value) => *value,
+            Value::Boolean(value) => f64::from(u8::from(*value)),
+        }
+    }
+
+    fn boolean(&self, value: &Value) -> bool {
+        match value {
+            Value::Nodes(nodes) => !nodes.is_empty(),
+            Value::String(value) => !value.is_empty(),
+            Value::Number(value) => *value != 0.0 && !value.is_nan(),
+            Value::Boolean(value) => *value,
+        }
+    }
+
+    fn compare(&self, op: CompareOp, left: &Value, right: &Value) -> bool {
+        match (left, right) {
+            (Value::Nodes(left), Value::Nodes(right)) => {
+                let right: Vec<Value> = right
+                    .iter()
+                    .map(|&node| Value::String(self.string_value(node)))
+                    .collect();
+                left.iter().any(|&node| {
+                    let left = Value::String(self.string_value(node));
+                    right
+                        .iter()
+                        .any(|right| self.compare_atomic(op, &left, right))
+                })
+            }
+            (Value::Nodes(_), Value::Boolean(_)) | (Value::Boolean(_), Value::Nodes(_)) => self
+                .compare_atomic(
+                    op,
+                    &Value::Boolean(self.boolean(left)),
+                    &Value::Boolean(self.boolean(right)),
+                ),
+            (Value::Nodes(nodes), other) => nodes.iter().any(|&node| {
+                self.compare_atomic(op, &Value::String(self.string_value(node)), other)
+            }),
+            (other, Value::Nodes(nodes)) => nodes.iter().any(|&node| {
+                self.compare_atomic(op, other, &Value::String(self.string_value(node)))
+            }),
+            _ => self.compare_atomic(op, left, right),
+        }
+    }
+
+    /// Compares two values that are not node-sets.
+    fn compare_atomic(&self, op: CompareOp, left: &Value, right: &Value) -> bool {
+        let equal = match op {
+            CompareOp::Equal => true,
+            CompareOp::NotEqual => false,
+            _ => {
+                let left = self.number(left);
+                let right = self.number(right);
+                return match op {
+                    CompareOp::Less => left < right,
+                    CompareOp::LessEqual => left <= right,
+                    CompareOp::Greater => left > right,
+                    _ => left >= right,
+                };
+            }
+        };
+
+        let same = match (left, right) {
+            (Value::Boolean(_), _) | (_, Value::Boolean(_)) => {
+                self.boolean(left) == self.boolean(right)
+            }
+            (Value::Number(_), _) | (_, Value::Number(_)) => {
+                self.number(left) == self.number(right)
+            }
+            _ => self.string(left) == self.string(right),
+        };
+        same == equal
+    }
+
+    fn call(
+        &self,
+        function: Function,
+        args: &[Expr],
+        context: &Context,
+    ) -> Result<Value, SpiderError> {
+        let string_arg = |index: usize| -> Result<String, SpiderError> {
+            match args.get(index) {
+                Some(arg) => Ok(self.string(&self.eval(arg, context)?)),
+                None => Ok(self.string_value(context.node)),
+            }
+        };
+        let number_arg = |index: usize| -> Result<f64, SpiderError> {
+            Ok(self.number(&self.eval(&args[index], context)?))
+        };
+        // The first node of the argument, or the context node.
+        let node_arg = |index: usize| -> Result<Option<XNode>, SpiderError> {
+            match args.get(index) {
+                Some(arg) => Ok(self.node_set(arg, context)?.first().copied()),
+                None => Ok(Some(context.node)),
+            }
+        };
+
+        Ok(match function {
+            Function::Last => Value::Number(context.size as f64),
+            Function::Position => Value::Number(context.position as f64),
+            Function::Count => Value::Number(self.node_set(&args[0], context)?.len() as f64),
+            Function::Id => {
+                let values = match self.eval(&args[0], context)? {
+                    Value::Nodes(nodes) => nodes
+                        .into_iter()
+                        .map(|node| self.string_value(node))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    other => self.string(&other),
+                };
+                let ids: HashSet<&str> = values.split_whitespace().collect();
+                Value::Nodes(
+                    self.html
+                        .tree
+                        .root()
+                        .descendants()
+                        .filter(|node| match node.value() {
+                            Node::Element(element) => {
+                                element.id().is_some_and(|id| ids.contains(id))
+                            }
+                            _ => false,
+                        })
+                        .map(|node| XNode::Node(node.id()))
+                        .collect(),
+                )
+            }
+            Function::LocalName | Function::Name | Function::NamespaceUri => {
+                let name = node_arg(0)?.and_then(|node| self.name(node));
+                Value::String(match (function, name) {
+                    (_, None) => String::new(),
+                    (Function::LocalName, Some(name)) => name.local.to_string(),
+                    (Function::NamespaceUri, Some(name)) => name.ns.to_string(),
+                    (_, Some(name)) => match &name.prefix {
+                        Some(prefix) => format!("{}:{}", prefix, name.local),
+                        None => name.local.to_string(),
+                    },
+                })
+            }
+            Function::String => Value::String(string_arg(0)?),
+            Function::Concat => Value::String(
+                (0..args.len())
+                    .map(string_arg)
+                    .collect::<Result<String, _>>()?,
+            ),
+            Function::StartsWith => Value::Boolean(string_arg(0)?.starts_with(&string_arg(1)?)),
+            Function::Contains => Value::Boolean(string_arg(0)?.contains(&string_arg(1)?)),
+            Function::SubstringBefore => {
+                let value = string_arg(0)?;
+                let needle = string_arg(1)?;
+                Value::String(match value.find(&needle) {
+                    Some(index) => value[..index].to_owned(),
+                    None => String::new(),
+                })
+            }
+            Function::SubstringAfter => {
+                let value = string_arg(0)?;
+                let needle = string_arg(1)?;
+                Value::String(match value.find(&needle) {
+                    Some(index) => value[index + needle.len()..].to_owned(),
+                    None => String::new(),
+                })
+            }
+            Function::Substring => {
+                let value = string_arg(0)?;
+                let start = round(number_arg(1)?);
+                let end = match args.len() {
+                    3 => start + round(number_arg(2)?),
+                    _ => f64::INFINITY,
+                };
+                Value::String(
+                    value
+                        .chars()
+                        .enumerate()
+                        .filter(|&(index, _)| {
+                            let position = (index + 1) as f64;
+                            position >= start && position < end
+                        })
+                        .map(|(_, c)| c)
+                        .collect(),
+                )
+            }
+            Function::StringLength => Value::Number(string_arg(0)?.chars().count() as f64),
+            Function::NormalizeSpace => Value::String(
+                string_arg(0)?
+                    .split(is_xml_whitespace)
+                    .filter(|word| !word.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Function::Translate => {
+                let value = string_arg(0)?;
+                let from: Vec<char> = string_arg(1)?.chars().collect();
+                let to: Vec<char> = string_arg(2)?.chars().collect();
+                Value::String(
+                    value
+                        .chars()
+                        .filter_map(|c| match from.iter().position(|&f| f == c) {
+                            Some(index) => to.get(index).copied(),
+                            None => Some(c),
+                        })
+                        .collect(),
+                )
+            }
+            Function::Boolean => Value::Boolean(self.boolean(&self.eval(&args[0], context)?)),
+            Function::Not => Value::Boolean(!self.boolean(&self.eval(&args[0], context)?)),
+            Function::True => Value::Boolean(true),
+            Function::False => Value::Boolean(false),
+            Function::Lang => {
+                let lang = string_arg(0)?.to_lowercase();
+                Value::Boolean(self.lang(context.node).is_some_and(|value| {
+                    let value = value.to_lowercase();
+                    value == lang
+                        || value
+                            .strip_prefix(&lang)
+                            .is_some_and(|rest| rest.starts_with('-'))
+                }))
+            }
+            Function::Number => Value::Number(match args.first() {
+                Some(arg) => self.number(&self.eval(arg, context)?),
+                None => string_to_number(&self.string_value(context.node)),
+            }),
+            Function::Sum => Value::Number(
+                self.node_set(&args[0], context)?
+                    .into_iter()
+                    .map(|node| string_to_number(&self.string_value(node)))
+                    .sum(),
+            ),
+            Function::Floor => Value::Number(number_arg(0)?.floor()),
+            Function::Ceiling => Value::Number(number_arg(0)?.ceil()),
+            Function::Round => Value::Number(round(number_arg(0)?)),
+            Function::HasClass => {
+                let classes: Vec<String> =
+                    (0..args.len()).map(string_arg).collect::<Result<_, _>>()?;
+                let has_classes = match context.node {
+                    XNode::Node(id) => match self.get(id).map(|node| node.value()) {
+                        Some(Node::Element(element)) => classes.iter().all(|class| {
+                            element
+                                .attr("class")
+                                .is_some_and(|value| value.split_whitespace().any(|c| c == class))
+                        }),
+                        _ => false,
+                    },
+                    XNode::Attribute(..) => false,
+                };
+                Value::Boolean(has_classes)
+            }
+        })
+    }
+
+    fn name(&self, node: XNode) -> Option<QualName> {
+        match node {
+            XNode::Attribute(id, index) => self.attribute(id, index).map(|(name, _)| name.clone()),
+            XNode::Node(id) => match self.get(id)?.value() {
+                Node::Element(element) => Some(element.name.clone()),
+                Node::ProcessingInstruction(pi) => Some(QualName::new(
+                    None,
+                    Namespace::from(""),
+                    LocalName::from(&*pi.target),
+                )),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the `xml:lang` or `lang` attribute in scope at a node.
+    fn lang(&self, node: XNode) -> Option<&'a str> {
+        let id = match node {
+            XNode::Node(id) | XNode::Attribute(id, _) => id,
+        };
+        let node = self.get(id)?;
+        std::iter::once(node)
+            .chain(node.ancestors())
+            .find_map(|node| match node.value() {
+                Node::Element(element) => element
+                    .attrs
+                    .iter()
+                    .find(|(name, _)| &*name.local == "lang")
+                    .map(|(_, value)| &**value),
+                _ => None,
+            })
+    }
+}
+
+fn is_xml_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+fn string_to_number(value: &str) -> f64 {
+    let value = value.trim_matches(is_xml_whitespace);
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let valid = !digits.is_empty()
+        && digits != "."
+        && digits.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+        && digits.bytes().filter(|&b| b == b'.').count() <= 1;
+    if valid {
+        value.parse().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+fn number_to_string(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "Infinity".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Infinity".to_string()
+    } else if value == 0.0 {
+        "0".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Rounds half up, as XPath's `round()` does.
+fn round(value: f64) -> f64 {
+    if value.is_nan() || value.is_infinite() {
+        return value;
+    }
+    let rounded = (value + 0.5).floor();
+    if rounded == 0.0 && value < 0.0 {
+        -0.0
+    } else {
+        rounded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::ReentrantMutex;
+
+    const LIST: &str = "<ul><li>1</li><li>2</li><li>3</li><li>4</li></ul>";
+
+    fn eval(html: &str, expr: &str) -> XPathValue {
+        let document = Arc::new(ReentrantMutex::new(Html::parse_document(html)));
+        evaluate(document, &XPath::compile(expr).unwrap()).unwrap()
+    }
+
+    fn strings(html: &str, expr: &str) -> Vec<String> {
+        eval(html, expr).into_nodes().unwrap().get_all()
+    }
+
+    fn string(expr: &str) -> String {
+        eval("", &format!("string({expr})"))
+            .as_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    #[test]
+    fn reverse_axes_count_positions_from_the_context_node() {
+        assert_eq!(
+            strings(LIST, "//li[3]/preceding-sibling::li[1]/text()"),
+            ["2"]
+        );
+        assert_eq!(
+            strings(LIST, "//li[3]/preceding-sibling::li[last()]/text()"),
+            ["1"]
+        );
+        assert_eq!(
+            strings(LIST, "//li[2]/following-sibling::li[1]/text()"),
+            ["3"]
+        );
+        assert_eq!(
+            eval("<div><p><b>x</b></p></div>", "name(//b/ancestor::*[1])").as_str(),
+            Some("p")
+        );
+        assert_eq!(
+            eval("<div><p><b>x</b></p></div>", "name(//b/ancestor::*[2])").as_str(),
+            Some("div")
+        );
+    }
+
+    #[test]
+    fn results_are_in_document_order() {
+        assert_eq!(
+            strings(LIST, "//li[3]/preceding-sibling::li/text()"),
+            ["1", "2"]
+        );
+        assert_eq!(
+            strings(LIST, "(//li[3]/preceding-sibling::li)[1]/text()"),
+            ["1"]
+        );
+        assert_eq!(
+            strings(LIST, "(//li[4] | //li[1] | //li[4])/text()"),
+            ["1", "4"]
+        );
+        assert_eq!(
+            strings(
+                "<div><p>a<b>b</b></p>c</div>",
+                "//b/ancestor-or-self::*/text()"
+            ),
+            ["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn positional_predicates() {
+        assert_eq!(strings(LIST, "//li[2]/text()"), ["2"]);
+        assert_eq!(strings(LIST, "//li[last()]/text()"), ["4"]);
+        assert_eq!(strings(LIST, "//li[position() > 2]/text()"), ["3", "4"]);
+        assert_eq!(
+            strings(LIST, "//li[position() mod 2 = 1]/text()"),
+            ["1", "3"]
+        );
+        assert_eq!(strings(LIST, "//li[position() > 1][2]/text()"), ["3"]);
+        assert_eq!(strings(LIST, "//li[5]"), Vec::<String>::new());
+        assert_eq!(
+            eval(LIST, "count(//li[position() < 3])").as_number(),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn abbreviated_descendant_keeps_predicates_per_parent() {
+        let html = "<div><p>a</p><p>b</p></div><div><p>c</p></div>";
+        assert_eq!(strings(html, "//p[1]/text()"), ["a", "c"]);
+        assert_eq!(strings(html, "/descendant::p[1]/text()"), ["a"]);
+        assert_eq!(strings(html, "(//p)[1]/text()"), ["a"]);
+        assert_eq!(strings(html, "//p/text()"), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn substring_edge_cases() {
+        assert_eq!(string("substring('12345', 2, 3)"), "234");
+        assert_eq!(string("substring('12345', 2)"), "2345");
+        assert_eq!(string("substring('12345', 1.5, 2.6)"), "234");
+        assert_eq!(string("substring('12345', 0, 3)"), "12");
+        assert_eq!(string("substring('12345', -1, 3)"), "1");
+        assert_eq!(string("substring('12345', 0 div 0, 3)"), "");
+        assert_eq!(string("substring('12345', 1, 0 div 0)"), "");
+        assert_eq!(string("substring('12345', -42, 1 div 0)"), "12345");
+        assert_eq!(string("substring('12345', -1 div 0, 1 div 0)"), "");
+    }
+
+    #[test]
+    fn round_edge_cases() {
+        assert_eq!(string("round(2.5)"), "3");
+        assert_eq!(string("round(-2.5)"), "-2");
+        assert_eq!(string("round(-0.4)"), "0");
+        assert_eq!(string("1 div round(-0.4)"), "-Infinity");
+        assert_eq!(string("round(0 div 0)"), "NaN");
+        assert_eq!(string("round(1 div 0)"), "Infinity");
+    }
+
+    #[test]
+    fn attribute_results() {
+        let html = r#"<a href="/x" title="X">x</a><a href="/y">y</a><a>z</a>"#;
+        assert_eq!(strings(html, "//a/@href"), ["/x", "/y"]);
+        // The order of attributes is implementation-defined.
+        let mut attributes = strings(html, "//a[@title]/@*");
+        attributes.sort();
+        assert_eq!(attributes, ["/x", "X"]);
+        assert_eq!(strings(html, "//a[not(@href)]/text()"), ["z"]);
+        assert_eq!(strings(html, "//a[@href = '/y']/text()"), ["y"]);
+        assert_eq!(eval(html, "string(//a/@href)").as_str(), Some("/x"));
+        assert_eq!(eval(html, "name(//a/@title)").as_str(), Some("title"));
+        assert_eq!(eval(html, "count(//@href)").as_number(), Some(2.0));
+        assert_eq!(strings(html, "//a/@href/../text()"), ["x", "y"]);
+        assert_eq!(strings(html, "//@title/parent::a/text()"), ["x"]);
+    }
+}